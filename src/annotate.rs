@@ -1,8 +1,135 @@
 //! Annotation APIs.
 
 use crate::query::Query;
+use crate::search::SearchResult;
+use crate::units::Millis;
 use crate::{Album, Artist, Client, Error, Result, Song};
 
+/// A selection of songs, albums, and artists to star or unstar at once.
+///
+/// Use with [`Client::star`] and [`Client::unstar`] to change the whole
+/// selection in a single request.
+///
+/// # Examples
+///
+/// ```no_run
+/// use sunk::annotate::StarRequest;
+/// use sunk::Client;
+///
+/// # async fn run() -> sunk::Result<()> {
+/// # let client = Client::new("http://demo.subsonic.org", "guest3", "guest")?;
+/// let selection = StarRequest::new().songs(&["27", "28"]).albums(&["1"]);
+/// client.star(&selection).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Client::star`]: ../struct.Client.html#method.star
+/// [`Client::unstar`]: ../struct.Client.html#method.unstar
+#[derive(Debug, Clone, Default)]
+pub struct StarRequest {
+    songs: Vec<String>,
+    albums: Vec<String>,
+    artists: Vec<String>,
+}
+
+impl StarRequest {
+    /// Creates an empty selection.
+    pub fn new() -> StarRequest {
+        StarRequest::default()
+    }
+
+    /// Adds songs (or other media files, or directories) by their ID.
+    pub fn songs(mut self, ids: &[&str]) -> StarRequest {
+        self.songs.extend(ids.iter().map(|id| id.to_string()));
+        self
+    }
+
+    /// Adds albums by their ID.
+    pub fn albums(mut self, ids: &[&str]) -> StarRequest {
+        self.albums.extend(ids.iter().map(|id| id.to_string()));
+        self
+    }
+
+    /// Adds artists by their ID.
+    pub fn artists(mut self, ids: &[&str]) -> StarRequest {
+        self.artists.extend(ids.iter().map(|id| id.to_string()));
+        self
+    }
+
+    /// Returns whether nothing has been selected.
+    pub fn is_empty(&self) -> bool {
+        self.songs.is_empty() && self.albums.is_empty() && self.artists.is_empty()
+    }
+
+    /// Returns the IDs of everything selected.
+    pub(crate) fn ids(&self) -> impl Iterator<Item = &str> {
+        self.songs
+            .iter()
+            .chain(&self.albums)
+            .chain(&self.artists)
+            .map(String::as_str)
+    }
+
+    fn len(&self) -> usize {
+        self.songs.len() + self.albums.len() + self.artists.len()
+    }
+
+    /// Splits everything in the search result into selections of at most
+    /// `size` items each.
+    pub(crate) fn batches(starred: &SearchResult, size: usize) -> Vec<StarRequest> {
+        fn next(batches: &mut Vec<StarRequest>, size: usize) -> &mut StarRequest {
+            if batches.last().map_or(true, |b| b.len() >= size) {
+                batches.push(StarRequest::new());
+            }
+            batches.last_mut().unwrap()
+        }
+
+        let mut batches = Vec::new();
+        for song in &starred.songs {
+            next(&mut batches, size).songs.push(song.id.clone());
+        }
+        for album in &starred.albums {
+            next(&mut batches, size).albums.push(album.id.clone());
+        }
+        for artist in &starred.artists {
+            next(&mut batches, size).artists.push(artist.id.clone());
+        }
+        batches
+    }
+
+    pub(crate) fn to_query(&self) -> Query {
+        Query::new()
+            .arg_list("id", &self.songs)
+            .arg_list("albumId", &self.albums)
+            .arg_list("artistId", &self.artists)
+            .build()
+    }
+}
+
+/// Annotations to apply to a song at once, with [`Client::annotate`].
+///
+/// Each annotation left as `None` is skipped.
+///
+/// [`Client::annotate`]: ../struct.Client.html#method.annotate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AnnotateOptions {
+    /// Stars the song if `true`, or unstars it if `false`.
+    pub star: Option<bool>,
+    /// Sets the rating of the song, from 1 to 5, or clears it if `0`.
+    pub rating: Option<u8>,
+    /// Scrobbles the song if `true`, or only reports it as now playing if
+    /// `false`.
+    pub scrobble: Option<bool>,
+}
+
+impl AnnotateOptions {
+    /// Returns whether no annotations are set.
+    pub fn is_empty(&self) -> bool {
+        self.star.is_none() && self.rating.is_none() && self.scrobble.is_none()
+    }
+}
+
 /// Allows starring, rating, and scrobbling media.
 #[async_trait::async_trait]
 pub trait Annotatable {
@@ -28,9 +155,9 @@ pub trait Annotatable {
     ///
     /// [`Client::now_playing()`]: ./struct.Client.html#method.now_playing
     ///
-    /// `time` should be a valid ISO8601 timestamp. In the future, this will be
-    /// validated.
-    async fn scrobble<'a, B: Send, T: Send>(
+    /// `time` is when the content was played, as the time since the Unix
+    /// epoch. It defaults to the current time.
+    async fn scrobble<B: Send, T: Send>(
         &self,
         client: &Client,
         time: T,
@@ -38,13 +165,14 @@ pub trait Annotatable {
     ) -> Result<()>
     where
         B: Into<Option<bool>>,
-        T: Into<Option<&'a str>>;
+        T: Into<Option<Millis>>;
 }
 
 #[async_trait::async_trait]
 impl Annotatable for Artist {
     async fn star(&self, client: &Client) -> Result<()> {
         client.get("star", Query::with("artistId", self.id.as_ref())).await?;
+        client.invalidate(&self.id);
         Ok(())
     }
 
@@ -52,6 +180,7 @@ impl Annotatable for Artist {
         client
             .get("unstar", Query::with("artistId", self.id.as_ref()))
             .await?;
+        client.invalidate(&self.id);
         Ok(())
     }
 
@@ -62,19 +191,21 @@ impl Annotatable for Artist {
 
         let args = Query::with("id", self.id.as_ref()).arg("rating", rating).build();
         client.get("setRating", args).await?;
+        client.invalidate(&self.id);
         Ok(())
     }
 
-    async fn scrobble<'a, B, T>(&self, client: &Client, time: T, now_playing: B) -> Result<()>
+    async fn scrobble<B, T>(&self, client: &Client, time: T, now_playing: B) -> Result<()>
     where
         B: Into<Option<bool>> + Send,
-        T: Into<Option<&'a str>> + Send,
+        T: Into<Option<Millis>> + Send,
     {
         let args = Query::with("id", self.id.as_ref())
             .arg("time", time.into())
             .arg("submission", now_playing.into().map(|b| !b))
             .build();
         client.get("scrobble", args).await?;
+        client.invalidate(&self.id);
         Ok(())
     }
 }
@@ -83,6 +214,7 @@ impl Annotatable for Artist {
 impl Annotatable for Album {
     async fn star(&self, client: &Client) -> Result<()> {
         client.get("star", Query::with("albumId", self.id.as_ref())).await?;
+        client.invalidate(&self.id);
         Ok(())
     }
 
@@ -90,6 +222,7 @@ impl Annotatable for Album {
         client
             .get("unstar", Query::with("albumId", self.id.as_ref()))
             .await?;
+        client.invalidate(&self.id);
         Ok(())
     }
 
@@ -100,19 +233,21 @@ impl Annotatable for Album {
 
         let args = Query::with("id", self.id.as_ref()).arg("rating", rating).build();
         client.get("setRating", args).await?;
+        client.invalidate(&self.id);
         Ok(())
     }
 
-    async fn scrobble<'a, B, T>(&self, client: &Client, time: T, now_playing: B) -> Result<()>
+    async fn scrobble<B, T>(&self, client: &Client, time: T, now_playing: B) -> Result<()>
     where
         B: Into<Option<bool>> + Send,
-        T: Into<Option<&'a str>> + Send,
+        T: Into<Option<Millis>> + Send,
     {
         let args = Query::with("id", self.id.as_ref())
             .arg("time", time.into())
             .arg("submission", now_playing.into().map(|b| !b))
             .build();
         client.get("scrobble", args).await?;
+        client.invalidate(&self.id);
         Ok(())
     }
 }
@@ -121,11 +256,13 @@ impl Annotatable for Album {
 impl Annotatable for Song {
     async fn star(&self, client: &Client) -> Result<()> {
         client.get("star", Query::with("id", self.id.as_ref())).await?;
+        client.invalidate(&self.id);
         Ok(())
     }
 
     async fn unstar(&self, client: &Client) -> Result<()> {
         client.get("unstar", Query::with("id", self.id.as_ref())).await?;
+        client.invalidate(&self.id);
         Ok(())
     }
 
@@ -136,19 +273,59 @@ impl Annotatable for Song {
 
         let args = Query::with("id", self.id.as_ref()).arg("rating", rating).build();
         client.get("setRating", args).await?;
+        client.invalidate(&self.id);
         Ok(())
     }
 
-    async fn scrobble<'a, B, T>(&self, client: &Client, time: T, now_playing: B) -> Result<()>
+    async fn scrobble<B, T>(&self, client: &Client, time: T, now_playing: B) -> Result<()>
     where
         B: Into<Option<bool>> + Send,
-        T: Into<Option<&'a str>> + Send,
+        T: Into<Option<Millis>> + Send,
     {
         let args = Query::with("id", self.id.as_ref())
             .arg("time", time.into())
             .arg("submission", now_playing.into().map(|b| !b))
             .build();
         client.get("scrobble", args).await?;
+        client.invalidate(&self.id);
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn star_request_query() {
+        let req = StarRequest::new()
+            .songs(&["27", "28"])
+            .artists(&["4"])
+            .albums(&["1"]);
+        assert_eq!(
+            req.to_query().to_string(),
+            "id=27&id=28&albumId=1&artistId=4"
+        );
+        assert!(StarRequest::new().is_empty());
+    }
+
+    #[test]
+    fn batch_starred() {
+        let starred = serde_json::from_value::<SearchResult>(serde_json::json!({
+            "song": [
+                { "id": "27", "title": "Bellevue Avenue", "size": 1, "contentType": "audio/mpeg", "suffix": "mp3", "type": "music" },
+                { "id": "28", "title": "Grey Street", "size": 1, "contentType": "audio/mpeg", "suffix": "mp3", "type": "music" }
+            ],
+            "album": [ { "id": "1", "name": "1999", "duration": 0, "songCount": 2 } ],
+            "artist": [ { "id": "4", "name": "Misteur Valaire", "albumCount": 1 } ]
+        }))
+        .unwrap();
+
+        let batches = StarRequest::batches(&starred, 3);
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].to_query().to_string(), "id=27&id=28&albumId=1");
+        assert_eq!(batches[1].to_query().to_string(), "artistId=4");
+        let none = serde_json::from_str::<SearchResult>("{}").unwrap();
+        assert!(StarRequest::batches(&none, 3).is_empty());
+    }
+}
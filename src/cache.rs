@@ -0,0 +1,77 @@
+//! A short-lived cache of fetched artists, albums, and songs.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Responses for single entities, keyed by the endpoint and the entity's ID.
+///
+/// Entries are kept for a fixed time, and dropped early when the entity with
+/// their ID is changed through the client.
+#[derive(Debug)]
+pub(crate) struct EntityCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<(String, String), (Instant, serde_json::Value)>>,
+}
+
+impl EntityCache {
+    pub(crate) fn new(ttl: Duration) -> EntityCache {
+        EntityCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the response cached for the entity, if it hasn't expired.
+    pub(crate) fn get(&self, endpoint: &str, id: &str) -> Option<serde_json::Value> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(&(endpoint.to_string(), id.to_string()))
+            .filter(|(at, _)| at.elapsed() < self.ttl)
+            .map(|(_, value)| value.clone())
+    }
+
+    /// Caches the response for the entity, dropping any that have expired.
+    pub(crate) fn insert(&self, endpoint: &str, id: &str, value: serde_json::Value) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (at, _)| at.elapsed() < self.ttl);
+        entries.insert(
+            (endpoint.to_string(), id.to_string()),
+            (Instant::now(), value),
+        );
+    }
+
+    /// Drops every response cached for entities with the ID.
+    pub(crate) fn invalidate(&self, id: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|(_, cached), _| cached != id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_and_invalidate() {
+        let cache = EntityCache::new(Duration::from_secs(60));
+        cache.insert("getAlbum", "1", serde_json::json!({ "name": "Bellevue" }));
+        cache.insert("getArtist", "1", serde_json::json!({ "name": "Misteur" }));
+        cache.insert("getSong", "27", serde_json::json!({ "title": "Avenue" }));
+
+        assert_eq!(cache.get("getAlbum", "1").unwrap()["name"], "Bellevue");
+        assert!(cache.get("getSong", "1").is_none());
+
+        cache.invalidate("1");
+        assert!(cache.get("getAlbum", "1").is_none());
+        assert!(cache.get("getArtist", "1").is_none());
+        assert!(cache.get("getSong", "27").is_some());
+    }
+
+    #[test]
+    fn cache_expires() {
+        let cache = EntityCache::new(Duration::ZERO);
+        cache.insert("getSong", "27", serde_json::json!({ "title": "Avenue" }));
+        assert!(cache.get("getSong", "27").is_none());
+    }
+}
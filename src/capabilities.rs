@@ -0,0 +1,292 @@
+//! Server feature detection APIs.
+
+use crate::{Album, ListType, StreamFormat, Version};
+
+/// An OpenSubsonic extension advertised by a server.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Extension {
+    /// The name of the extension, such as `"songLyrics"`.
+    pub name: String,
+    /// The versions of the extension the server supports.
+    pub versions: Vec<u32>,
+}
+
+/// The server software behind a Subsonic API, from [`Client::server_flavor`].
+///
+/// Servers implement the API with small differences. The client uses the
+/// flavor to smooth over the ones it knows about, so that results look the
+/// same whichever server they came from.
+///
+/// [`Client::server_flavor`]: ../struct.Client.html#method.server_flavor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerFlavor {
+    /// The original Subsonic server, or a server that doesn't say what it is.
+    Subsonic,
+    /// Navidrome.
+    Navidrome,
+    /// Airsonic or Airsonic-Advanced.
+    Airsonic,
+    /// Gonic.
+    Gonic,
+    /// Another OpenSubsonic server.
+    Other,
+}
+
+impl ServerFlavor {
+    /// Detects the flavor from the `type` a server reports in its responses.
+    pub(crate) fn from_type(server_type: Option<&str>) -> ServerFlavor {
+        let server_type = match server_type {
+            Some(t) => t.to_lowercase(),
+            None => return ServerFlavor::Subsonic,
+        };
+        match server_type.as_str() {
+            "subsonic" => ServerFlavor::Subsonic,
+            "navidrome" => ServerFlavor::Navidrome,
+            "gonic" => ServerFlavor::Gonic,
+            t if t.starts_with("airsonic") => ServerFlavor::Airsonic,
+            _ => ServerFlavor::Other,
+        }
+    }
+
+    /// Returns the formats the server is likely to transcode to out of the
+    /// box, most widely supported first.
+    ///
+    /// This is a guess from each server's default setup; administrators can
+    /// add or remove transcoders, and the API has no way to list them.
+    pub fn transcode_formats(self) -> &'static [StreamFormat] {
+        match self {
+            ServerFlavor::Subsonic => &[StreamFormat::Mp3],
+            ServerFlavor::Navidrome => &[StreamFormat::Mp3, StreamFormat::Opus, StreamFormat::Aac],
+            ServerFlavor::Airsonic | ServerFlavor::Gonic | ServerFlavor::Other => {
+                &[StreamFormat::Mp3, StreamFormat::Opus]
+            }
+        }
+    }
+
+    /// Fixes the order within a page of an album list up to the one the API
+    /// documents.
+    ///
+    /// Lists [by year](../enum.ListType.html#variant.ByYear) are meant to
+    /// run from `from` to `to`, counting down if `from` is the later year.
+    /// Gonic swaps such a range and lists it counting up, so each of its
+    /// pages is put back in descending order, keeping the order within each
+    /// year. The pages themselves still run from the oldest albums, as
+    /// Gonic doesn't say how many albums there are to page back from.
+    pub(crate) fn fix_album_list(self, list_type: ListType, albums: &mut [Album]) {
+        if let ListType::ByYear { from, to } = list_type {
+            if self == ServerFlavor::Gonic && from > to {
+                albums.sort_by_key(|a| std::cmp::Reverse(a.year.unwrap_or_default()));
+            }
+        }
+    }
+}
+
+/// The features a server supports, from [`Client::capabilities`].
+///
+/// Features are derived from the API version the server reports and the
+/// OpenSubsonic extensions it advertises. Servers that don't implement
+/// OpenSubsonic advertise no extensions.
+///
+/// [`Client::capabilities`]: ../struct.Client.html#method.capabilities
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    version: Version,
+    extensions: Vec<Extension>,
+}
+
+impl Capabilities {
+    pub(crate) fn new(version: Version, extensions: Vec<Extension>) -> Capabilities {
+        Capabilities {
+            version,
+            extensions,
+        }
+    }
+
+    /// Returns the API version the server reported.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Returns the OpenSubsonic extensions the server advertised.
+    pub fn extensions(&self) -> &[Extension] {
+        &self.extensions
+    }
+
+    /// Returns whether the server advertised the named OpenSubsonic
+    /// extension.
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.iter().any(|e| e.name == name)
+    }
+
+    fn since(&self, ver: &str) -> bool {
+        self.version >= ver.into()
+    }
+
+    /// Whether the server supports token authentication (since 1.13.0).
+    pub fn supports_token_auth(&self) -> bool {
+        self.since("1.13.0")
+    }
+
+    /// Whether the server organises media by ID3 tags (since 1.8.0).
+    pub fn supports_id3(&self) -> bool {
+        self.since("1.8.0")
+    }
+
+    /// Whether the server allows creating and editing playlists (since
+    /// 1.2.0).
+    pub fn supports_playlists(&self) -> bool {
+        self.since("1.2.0")
+    }
+
+    /// Whether the server supports podcasts (since 1.6.0).
+    pub fn supports_podcasts(&self) -> bool {
+        self.since("1.6.0")
+    }
+
+    /// Whether the server supports bookmarks (since 1.9.0).
+    pub fn supports_bookmarks(&self) -> bool {
+        self.since("1.9.0")
+    }
+
+    /// Whether the server can save a play queue (since 1.12.0).
+    pub fn supports_play_queue(&self) -> bool {
+        self.since("1.12.0")
+    }
+
+    /// Whether the server can find lyrics by song ID, rather than by artist
+    /// and title. Requires the `songLyrics` extension.
+    pub fn supports_lyrics_by_song_id(&self) -> bool {
+        self.has_extension("songLyrics")
+    }
+
+    /// Whether the server accepts API keys in place of a user and password.
+    /// Requires the `apiKeyAuthentication` extension.
+    pub fn supports_api_key_auth(&self) -> bool {
+        self.has_extension("apiKeyAuthentication")
+    }
+
+    /// Whether the server can start transcoded streams at an offset.
+    /// Requires the `transcodeOffset` extension.
+    pub fn supports_transcode_offset(&self) -> bool {
+        self.has_extension("transcodeOffset")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capabilities_from_version() {
+        let caps = Capabilities::new("1.10.2".into(), Vec::new());
+
+        assert!(caps.supports_bookmarks());
+        assert!(caps.supports_id3());
+        assert!(!caps.supports_play_queue());
+        assert!(!caps.supports_token_auth());
+        assert!(!caps.supports_lyrics_by_song_id());
+    }
+
+    #[test]
+    fn capabilities_from_extensions() {
+        let extensions = serde_json::from_str(
+            r#"[
+            { "name" : "songLyrics", "versions" : [ 1 ] },
+            { "name" : "formPost", "versions" : [ 1 ] }
+        ]"#,
+        )
+        .unwrap();
+        let caps = Capabilities::new("1.16.1".into(), extensions);
+
+        assert!(caps.supports_lyrics_by_song_id());
+        assert!(!caps.supports_transcode_offset());
+        assert!(caps.has_extension("formPost"));
+    }
+
+    #[test]
+    fn detect_server_flavor() {
+        assert_eq!(ServerFlavor::from_type(None), ServerFlavor::Subsonic);
+        assert_eq!(
+            ServerFlavor::from_type(Some("navidrome")),
+            ServerFlavor::Navidrome
+        );
+        assert_eq!(
+            ServerFlavor::from_type(Some("airsonic-advanced")),
+            ServerFlavor::Airsonic
+        );
+        assert_eq!(ServerFlavor::from_type(Some("Gonic")), ServerFlavor::Gonic);
+        assert_eq!(ServerFlavor::from_type(Some("lms")), ServerFlavor::Other);
+    }
+
+    #[test]
+    fn fix_year_order() {
+        let mut albums: Vec<Album> = [2001, 1999, 2000]
+            .iter()
+            .map(|year| {
+                serde_json::from_value(serde_json::json!({
+                    "id": year.to_string(),
+                    "name": "Bellevue",
+                    "duration": 0,
+                    "songCount": 0,
+                    "year": year
+                }))
+                .unwrap()
+            })
+            .collect();
+        let years = |albums: &[Album]| albums.iter().map(|a| a.year.unwrap()).collect::<Vec<_>>();
+
+        let descending = ListType::ByYear {
+            from: 2001,
+            to: 1999,
+        };
+        ServerFlavor::Subsonic.fix_album_list(descending, &mut albums);
+        ServerFlavor::Navidrome.fix_album_list(descending, &mut albums);
+        assert_eq!(years(&albums), [2001, 1999, 2000]);
+
+        let ascending = ListType::ByYear {
+            from: 1999,
+            to: 2001,
+        };
+        ServerFlavor::Gonic.fix_album_list(ascending, &mut albums);
+        assert_eq!(years(&albums), [2001, 1999, 2000]);
+
+        ServerFlavor::Gonic.fix_album_list(descending, &mut albums);
+        assert_eq!(years(&albums), [2001, 2000, 1999]);
+    }
+
+    #[test]
+    fn fix_year_order_per_page() {
+        let page = |years: &[u32]| {
+            years
+                .iter()
+                .map(|year| {
+                    serde_json::from_value::<Album>(serde_json::json!({
+                        "id": year.to_string(),
+                        "name": "Bellevue",
+                        "duration": 0,
+                        "songCount": 0,
+                        "year": year
+                    }))
+                    .unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+        let descending = ListType::ByYear {
+            from: 2002,
+            to: 1999,
+        };
+
+        // Gonic pages through the range counting up, so only the order
+        // within each page can be fixed.
+        let mut pages = [page(&[1999, 2000]), page(&[2001, 2002])];
+        for albums in &mut pages {
+            ServerFlavor::Gonic.fix_album_list(descending, albums);
+        }
+        let years = pages
+            .iter()
+            .flatten()
+            .map(|a| a.year.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(years, [2000, 1999, 2002, 2001]);
+    }
+}
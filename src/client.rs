@@ -1,16 +1,48 @@
-use std::iter;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{fmt, iter};
 
+use base64::Engine;
+use futures_util::future;
+use futures_util::stream::{self, Stream, StreamExt};
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use reqwest::header::{HeaderMap, CONTENT_TYPE, LOCATION, RANGE};
 use reqwest::Client as ReqwestClient;
-use reqwest::Url;
+use reqwest::{redirect, StatusCode, Url};
+use serde::de::DeserializeOwned;
 
-use crate::media::NowPlaying;
-use crate::query::Query;
-use crate::response::Response;
-use crate::search::{SearchPage, SearchResult};
-use crate::{Error, Genre, Hls, Lyrics, MusicFolder, Result, UrlError, Version};
+use crate::annotate::{Annotatable, AnnotateOptions, StarRequest};
+use crate::cache::EntityCache;
+use crate::capabilities::{Capabilities, Extension, ServerFlavor};
+use crate::collections::{self, FolderScope, GenreSort};
+use crate::media::podcast::Episode;
+use crate::media::{MediaStream, NowPlaying, StreamFormat, StreamParams};
+use crate::metrics::{Counters, Metrics};
+use crate::query::{self, Query};
+use crate::response::{self, Response};
+use crate::search::{PagedSearch, SearchCounts, SearchHit, SearchPage, SearchResult, Tally};
+use crate::{
+    Album, ApiError, Artist, Error, Genre, Hls, IndexArtist, Indexes, ListType, Lyrics,
+    MusicFolder, Result, Song, UrlError, Version,
+};
 
-const SALT_SIZE: usize = 36; // Minimum 6 characters.
+const SALT_SIZE: usize = 36;
+const MIN_SALT_SIZE: usize = 6;
+const MAX_REDIRECTS: usize = 5;
+const COUNT_PAGE_SIZE: usize = 500;
+const COUNT_CAP: usize = 1000;
+const PODCAST_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const UNSTAR_BATCH_SIZE: usize = 100;
+const RATED_PAGE_SIZE: usize = 500;
+const STREAM_PAGE_SIZE: usize = 20;
+const ALL_PAGE_SIZE: usize = 500;
+const SONG_FETCH_CONCURRENCY: usize = 8;
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Query parameters that authenticate a request.
+const AUTH_KEYS: [&str; 5] = ["u", "p", "t", "s", "apiKey"];
+/// Endpoints that are sent the client's player ID, if it has one.
+const PLAYER_ENDPOINTS: [&str; 3] = ["stream", "hls", "scrobble"];
 
 /// A client to make requests to a Subsonic instance.
 ///
@@ -52,17 +84,211 @@ pub struct Client {
     url: Url,
     auth: SubsonicAuth,
     reqclient: ReqwestClient,
+    http: HttpConfig,
+    metrics: Arc<Counters>,
+    cache: Option<EntityCache>,
     /// Version that the `Client` supports.
     pub ver: Version,
     /// Version that the `Client` is targeting; currently only has an effect on
     /// the authentication method.
     pub target_ver: Version,
+    /// Version that the server reported during the last [`negotiate`] call.
+    ///
+    /// [`negotiate`]: #method.negotiate
+    pub server_ver: Option<Version>,
+    flavor: Option<ServerFlavor>,
+    capabilities: Option<Capabilities>,
+    page_size: Option<usize>,
+    pinned: bool,
+    player_id: Option<String>,
+    genre_names: Mutex<Option<GenreNames>>,
+    multi_genre: Mutex<Option<bool>>,
+    ignored_articles: Mutex<Option<Vec<String>>>,
+}
+
+/// Settings for the underlying HTTP client, kept so that it can be rebuilt
+/// when one changes.
+#[derive(Debug, Clone, Default)]
+struct HttpConfig {
+    pool_idle_timeout: Option<Duration>,
+    pool_max_idle_per_host: Option<usize>,
+    shared: Option<ReqwestClient>,
+}
+
+impl HttpConfig {
+    fn build(&self) -> Result<ReqwestClient> {
+        if let Some(ref shared) = self.shared {
+            return Ok(shared.clone());
+        }
+
+        // Redirects are followed manually so that authentication survives them.
+        let mut builder = ReqwestClient::builder().redirect(redirect::Policy::none());
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        Ok(builder.build()?)
+    }
+}
+
+/// The server's genre names, as kept by [`Client::genre_map`].
+#[derive(Debug)]
+struct GenreNames {
+    /// The spelling each lowercased name resolves to.
+    canonical: HashMap<String, String>,
+    /// Every spelling the server uses, including those merged away.
+    spellings: HashSet<String>,
+}
+
+impl GenreNames {
+    fn new(genres: Vec<Genre>) -> GenreNames {
+        let spellings = genres.iter().map(|g| g.name.clone()).collect();
+        let canonical = collections::tidy_genres(genres, None)
+            .into_iter()
+            .map(|g| (genre_key(&g.name), g.name))
+            .collect();
+        GenreNames {
+            canonical,
+            spellings,
+        }
+    }
+
+    /// Returns the server's spelling of a genre. Names the server uses as
+    /// given are kept, so that each spelling's songs can be reached.
+    fn resolve(&self, genre: &str) -> String {
+        if self.spellings.contains(genre) {
+            return genre.to_string();
+        }
+        self.canonical
+            .get(&genre_key(genre))
+            .cloned()
+            .unwrap_or_else(|| genre.to_string())
+    }
 }
 
 #[derive(Debug)]
 struct SubsonicAuth {
     user: String,
     password: String,
+    client_name: String,
+    xml: bool,
+    api_key: Option<String>,
+    salt_size: usize,
+    salt_charset: SaltCharset,
+}
+
+/// What is needed to log in again later, from [`Client::credentials`].
+///
+/// Token authentication is derived from the password afresh for every
+/// request, so the password (or API key) itself is what has to be kept.
+/// Only the server address, the user, and the client name are serialized;
+/// the caller is responsible for storing the secret somewhere safe, such as
+/// the system keyring, and attaching it again with [`with_password`] or
+/// [`with_api_key`] before calling [`Client::from_credentials`].
+///
+/// [`Client::credentials`]: ./struct.Client.html#method.credentials
+/// [`Client::from_credentials`]: ./struct.Client.html#method.from_credentials
+/// [`with_password`]: #method.with_password
+/// [`with_api_key`]: #method.with_api_key
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Credentials {
+    /// The address of the server.
+    pub url: String,
+    /// The user to log in as.
+    pub user: String,
+    /// The name the client identifies itself with to the server.
+    pub client_name: String,
+    /// The player ID sent with streams and scrobbles, if one was set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player_id: Option<String>,
+    #[serde(skip)]
+    password: Option<String>,
+    #[serde(skip)]
+    api_key: Option<String>,
+}
+
+impl Credentials {
+    /// Returns the user's password, unless the credentials were deserialized
+    /// and it hasn't been attached again.
+    pub fn password(&self) -> Option<&str> {
+        self.password.as_deref()
+    }
+
+    /// Returns the API key the client authenticates with, if it has one.
+    pub fn api_key(&self) -> Option<&str> {
+        self.api_key.as_deref()
+    }
+
+    /// Attaches the user's password.
+    pub fn with_password(self, password: &str) -> Credentials {
+        let mut creds = self;
+        creds.password = Some(password.to_string());
+        creds
+    }
+
+    /// Attaches an API key to authenticate with instead of the password.
+    pub fn with_api_key(self, key: &str) -> Credentials {
+        let mut creds = self;
+        creds.api_key = Some(key.to_string());
+        creds
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hidden = |secret: &Option<String>| secret.as_ref().map(|_| "<redacted>");
+        f.debug_struct("Credentials")
+            .field("url", &self.url)
+            .field("user", &self.user)
+            .field("client_name", &self.client_name)
+            .field("password", &hidden(&self.password))
+            .field("api_key", &hidden(&self.api_key))
+            .finish()
+    }
+}
+
+/// The body of a binary endpoint, from [`Client::get_binary`].
+///
+/// [`Client::get_binary`]: ./struct.Client.html#method.get_binary
+#[derive(Debug, Clone)]
+pub struct BinaryResponse {
+    /// The body, exactly as the server sent it.
+    pub data: Vec<u8>,
+    /// The MIME type the server gave the body, if any.
+    pub content_type: Option<String>,
+}
+
+/// The characters that authentication salts are made of.
+///
+/// The Subsonic API doesn't restrict salts, but some servers reject salts
+/// with certain characters. Set with [`Client::with_salt_charset`].
+///
+/// [`Client::with_salt_charset`]: ./struct.Client.html#method.with_salt_charset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SaltCharset {
+    /// Upper- and lowercase letters and digits.
+    #[default]
+    Alphanumeric,
+    /// Lowercase hexadecimal digits.
+    Hex,
+}
+
+impl SaltCharset {
+    fn generate<R: Rng>(self, rng: &mut R, len: usize) -> String {
+        match self {
+            SaltCharset::Alphanumeric => iter::repeat_with(|| char::from(rng.sample(Alphanumeric)))
+                .take(len)
+                .collect(),
+            SaltCharset::Hex => iter::repeat_with(|| {
+                char::from_digit(rng.gen_range(0..16), 16).expect("digit is below 16")
+            })
+            .take(len)
+            .collect(),
+        }
+    }
 }
 
 impl SubsonicAuth {
@@ -70,29 +296,67 @@ impl SubsonicAuth {
         SubsonicAuth {
             user: user.into(),
             password: password.into(),
+            client_name: env!("CARGO_PKG_NAME").into(),
+            xml: false,
+            api_key: None,
+            salt_size: SALT_SIZE,
+            salt_charset: SaltCharset::default(),
         }
     }
 
     fn to_url(&self, ver: Version) -> String {
+        let salt = self
+            .salt_charset
+            .generate(&mut thread_rng(), self.salt_size);
+        self.to_url_with_salt(ver, &salt)
+    }
+
+    /// Builds the authentication parameters using a known salt. The salt is
+    /// ignored if the version doesn't support token authentication, or an API
+    /// key is used instead.
+    fn to_url_with_salt(&self, ver: Version, salt: &str) -> String {
+        let auth = if let Some(ref key) = self.api_key {
+            let key: String = url::form_urlencoded::byte_serialize(key.as_bytes()).collect();
+            format!("apiKey={key}")
         // First md5 support.
-        let auth = if ver >= "1.13.0".into() {
-            let mut rng = thread_rng();
-            let salt: String = iter::repeat(())
-                .take(SALT_SIZE)
-                .map(|_| char::from(rng.sample(Alphanumeric)))
-                .collect();
-            let pre_t = self.password.to_string() + &salt;
+        } else if ver >= "1.13.0".into() {
+            let pre_t = self.password.to_string() + salt;
             let token = format!("{:x}", md5::compute(pre_t.as_bytes()));
 
             format!("u={u}&t={t}&s={s}", u = self.user, t = token, s = salt)
         } else {
-            format!("u={u}&p={p}", u = self.user, p = self.password)
+            format!(
+                "u={u}&p={p}",
+                u = self.user,
+                p = legacy_password(&self.password)
+            )
         };
 
-        let format = "json";
-        let crate_name = env!("CARGO_PKG_NAME");
+        let format = if self.xml { "xml" } else { "json" };
+        let client: String =
+            url::form_urlencoded::byte_serialize(self.client_name.as_bytes()).collect();
+
+        format!("{auth}&v={ver}&c={client}&f={format}")
+    }
+}
 
-        format!("{auth}&v={ver}&c={crate_name}&f={format}")
+/// Formats a password to be sent in plain to a server before 1.13.0.
+///
+/// Passwords with characters that aren't safe in a URL, such as `&` or `=`,
+/// are hex-encoded with an `enc:` prefix, which every version of the API
+/// accepts. Other passwords are sent as-is.
+fn legacy_password(password: &str) -> String {
+    let safe = password
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b"-._~".contains(&b));
+    if safe {
+        password.to_string()
+    } else {
+        let hex = password
+            .bytes()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>();
+        format!("enc:{hex}")
     }
 }
 
@@ -106,17 +370,75 @@ impl Client {
         let ver = Version::from("1.14.0");
         let target_ver = ver;
 
-        let reqclient = ReqwestClient::builder().build()?;
+        let http = HttpConfig::default();
+        let reqclient = http.build()?;
 
         Ok(Client {
             url,
             auth,
             reqclient,
+            http,
+            metrics: Arc::default(),
+            cache: None,
             ver,
             target_ver,
+            server_ver: None,
+            flavor: None,
+            capabilities: None,
+            page_size: None,
+            pinned: false,
+            player_id: None,
+            genre_names: Mutex::new(None),
+            multi_genre: Mutex::new(None),
+            ignored_articles: Mutex::new(None),
+        })
+    }
+
+    /// Constructs a client from saved [`Credentials`], as the client they
+    /// were taken from was set up.
+    ///
+    /// Settings other than the client name, including the target version,
+    /// start at their defaults.
+    ///
+    /// [`Credentials`]: ./struct.Credentials.html
+    ///
+    /// # Errors
+    ///
+    /// Aside from the errors of [`new`](#method.new), errors if the
+    /// credentials have neither a password nor an API key attached.
+    pub fn from_credentials(creds: Credentials) -> Result<Client> {
+        if creds.password.is_none() && creds.api_key.is_none() {
+            return Err(Error::Other("credentials have no password or API key"));
+        }
+        let password = creds.password.unwrap_or_default();
+        let mut cli =
+            Client::new(&creds.url, &creds.user, &password)?.with_client_name(&creds.client_name);
+        cli.player_id = creds.player_id;
+        Ok(match creds.api_key {
+            Some(ref key) => cli.with_api_key(key),
+            None => cli,
         })
     }
 
+    /// Returns what is needed to recreate the client later with
+    /// [`from_credentials`](#method.from_credentials), including its
+    /// password or API key.
+    pub fn credentials(&self) -> Credentials {
+        Credentials {
+            url: self.url.to_string(),
+            user: self.auth.user.clone(),
+            client_name: self.auth.client_name.clone(),
+            player_id: self.player_id.clone(),
+            password: Some(self.auth.password.clone()).filter(|p| !p.is_empty()),
+            api_key: self.auth.api_key.clone(),
+        }
+    }
+
+    /// Returns a snapshot of the requests the client has made so far.
+    pub fn metrics(&self) -> Metrics {
+        self.metrics.snapshot()
+    }
+
     /// Adjusts the client to target a specific version.
     ///
     /// By default, the client will target version 1.14.0, as built by `sunk`.
@@ -126,192 +448,1164 @@ impl Client {
     /// an override on these features by making the client limit itself to
     /// features that the target will support.
     ///
+    /// Setting a target explicitly pins the version; [`negotiate`] will still
+    /// record the server's version, but will no longer change the target.
+    /// This is useful for testing compatibility with older servers, or for
+    /// avoiding a feature that is broken on a specific server.
+    ///
     /// Note that (currently) the client does not provide any sanity-checking
     /// on which methods are called; attempting to access an endpoint not
     /// supported by the server will fail after the call, not before.
+    ///
+    /// [`negotiate`]: #method.negotiate
     pub fn with_target(self, ver: Version) -> Client {
         let mut cli = self;
         cli.target_ver = ver;
+        cli.pinned = true;
         cli
     }
 
-    /// Internal helper function to construct a URL when the actual fetching is
-    /// not required.
-    #[cfg_attr(feature = "cargo-clippy", allow(clippy::needless_pass_by_value))]
-    pub(crate) fn build_url(&self, query: &str, args: Query) -> Result<String> {
-        let scheme = self.url.scheme();
-        let addr = self.url.host_str().ok_or(Error::Url(UrlError::Address))?;
-        let path = self.url.path();
-
-        let mut url = [scheme, "://", addr, path, "rest/"].concat();
-        url.push_str(query);
-        url.push('?');
-        url.push_str(&self.auth.to_url(self.target_ver));
-        url.push('&');
-        url.push_str(&args.to_string());
-
-        Ok(url)
+    /// Sets the name the client identifies itself with to the server.
+    ///
+    /// Servers use the name to tell apps apart, in logs and in the list of
+    /// players. The name can carry a version, such as `"myapp/1.2.3"`.
+    /// Defaults to `"sunk"`.
+    pub fn with_client_name(self, name: &str) -> Client {
+        let mut cli = self;
+        cli.auth.client_name = name.to_string();
+        cli
     }
 
-    /// Issues a request to the Subsonic server.
+    /// Sets the player ID sent with stream, HLS, and scrobble requests.
     ///
-    /// A query should be one documented in the [official API].
+    /// Servers that track players by ID use it to tie playback and the
+    /// "now playing" list to one player, rather than starting a new session
+    /// each time the app restarts. Keep the ID stable for a device, for
+    /// instance by saving it with the client's [`credentials`].
     ///
-    /// [official API]: http://www.subsonic.org/pages/api.jsp
+    /// [`credentials`]: #method.credentials
+    pub fn with_player_id(self, id: &str) -> Client {
+        let mut cli = self;
+        cli.player_id = Some(id.to_string()).filter(|id| !id.is_empty());
+        cli
+    }
+
+    /// Sets whether to ask the server for XML responses instead of JSON.
     ///
-    /// # Errors
+    /// Some older Subsonic builds send malformed JSON but valid XML. XML
+    /// responses are parsed into the same types, so nothing else changes.
+    /// Defaults to `false`.
+    pub fn with_prefer_xml(self, prefer: bool) -> Client {
+        let mut cli = self;
+        cli.auth.xml = prefer;
+        cli
+    }
+
+    /// Caches fetched artists, albums, and songs for `ttl`.
     ///
-    /// Will return an error if any of the following occurs:
+    /// Useful for interfaces that browse back and forth over the same items.
+    /// Starring, rating, or scrobbling an item through the client drops it
+    /// from the cache, so changes show up on the next fetch. Changes to an
+    /// item's songs don't drop the item itself, for instance starring a song
+    /// leaves a cached copy of its album as it was.
+    pub fn with_entity_cache_ttl(self, ttl: Duration) -> Client {
+        let mut cli = self;
+        cli.cache = Some(EntityCache::new(ttl));
+        cli
+    }
+
+    /// Sets how many results list methods ask for when the caller doesn't
+    /// say.
     ///
-    /// - server is built with an incomplete URL
-    /// - connecting to the server fails
-    /// - the server returns an API error
-    pub(crate) async fn get(&self, query: &str, args: Query) -> Result<serde_json::Value> {
-        let uri: Url = self.build_url(query, args)?.parse().unwrap();
+    /// Applies wherever a count or page size is passed as `None` or `0`,
+    /// such as [`Song::random`], [`Album::list`], and [`search_stream`].
+    /// Searches with an explicit [`SearchPage`] are unaffected, as a count of
+    /// `0` there skips that kind of result. Without a default, each method
+    /// keeps its own, or leaves the choice to the server.
+    ///
+    /// [`Song::random`]: ./struct.Song.html#method.random
+    /// [`Album::list`]: ./struct.Album.html#method.list
+    /// [`search_stream`]: #method.search_stream
+    /// [`SearchPage`]: ./search/struct.SearchPage.html
+    pub fn with_default_page_size(self, size: usize) -> Client {
+        let mut cli = self;
+        cli.page_size = Some(size).filter(|&s| s > 0);
+        cli
+    }
 
-        info!("Connecting to {}", uri);
-        let res = self.reqclient.get(uri).send().await?;
+    /// Returns the count to request for a list: the given one if set, or
+    /// else the client's default page size.
+    pub(crate) fn page_size<U: Into<Option<usize>>>(&self, count: U) -> Option<usize> {
+        count.into().filter(|&c| c > 0).or(self.page_size)
+    }
 
-        if res.status().is_success() {
-            let response = res.json::<Response>().await?;
-            if response.is_ok() {
-                Ok(match response.into_value() {
-                    Some(v) => v,
-                    None => serde_json::Value::Null,
-                })
-            } else {
-                Err(response
-                    .into_error()
-                    .map(|e| e.into())
-                    .ok_or(Error::Other("unable to retrieve error"))?)
-            }
-        } else {
-            Err(Error::Connection(res.status()))
-        }
+    /// Sets the length of the salt used for token authentication.
+    ///
+    /// Defaults to 36 characters. Lengths below 6 characters are raised to 6,
+    /// the least the Subsonic API allows.
+    pub fn with_salt_length(self, len: usize) -> Client {
+        let mut cli = self;
+        cli.auth.salt_size = len.max(MIN_SALT_SIZE);
+        cli
     }
 
-    /// Fetches an unprocessed response from the server rather than a JSON- or
-    /// XML-parsed one.
-    pub(crate) async fn get_raw(&self, query: &str, args: Query) -> Result<String> {
-        let uri: Url = self.build_url(query, args)?.parse().unwrap();
-        let res = self.reqclient.get(uri).send().await?;
-        Ok(res.text().await?)
+    /// Sets the characters used in the salt for token authentication.
+    /// Defaults to [`SaltCharset::Alphanumeric`].
+    ///
+    /// [`SaltCharset::Alphanumeric`]: ./enum.SaltCharset.html#variant.Alphanumeric
+    pub fn with_salt_charset(self, charset: SaltCharset) -> Client {
+        let mut cli = self;
+        cli.auth.salt_charset = charset;
+        cli
     }
 
-    /// Returns a response as a vector of bytes rather than serialising it.
-    pub(crate) async fn get_bytes(&self, query: &str, args: Query) -> Result<Vec<u8>> {
-        let uri: Url = self.build_url(query, args)?.parse().unwrap();
-        let res = self.reqclient.get(uri).send().await?;
-        Ok(res.bytes().await?.to_vec())
+    /// Authenticates with an API key instead of the user's password.
+    ///
+    /// API keys are supported by servers with the OpenSubsonic
+    /// `apiKeyAuthentication` extension, such as Navidrome. The key is used
+    /// for every request; if [`capabilities`] finds that the server doesn't
+    /// support it, the client falls back to the user and password it was
+    /// created with.
+    ///
+    /// [`capabilities`]: #method.capabilities
+    pub fn with_api_key(self, key: &str) -> Client {
+        let mut cli = self;
+        cli.auth.api_key = Some(key.to_string());
+        cli
     }
 
-    /// Returns the raw bytes of a HLS slice.
-    pub async fn hls_bytes(&self, hls: &Hls) -> Result<Vec<u8>> {
-        let url: Url = self
-            .url
-            .join(&hls.url)
-            .map_err(<url::ParseError as Into<UrlError>>::into)?;
-        let res = self.reqclient.get(url).send().await?;
-        Ok(res.bytes().await?.to_vec())
+    /// Sets how long an idle connection is kept open for re-use.
+    ///
+    /// Reverse proxies often close idle connections sooner than the client
+    /// expects, which makes the first request after a quiet period fail. A
+    /// timeout shorter than the proxy's avoids this. Defaults to 90 seconds.
+    pub fn with_pool_idle_timeout(self, timeout: Duration) -> Result<Client> {
+        let mut cli = self;
+        cli.http.pool_idle_timeout = Some(timeout);
+        cli.reqclient = cli.http.build()?;
+        Ok(cli)
     }
 
-    /// Tests a connection with the server.
-    pub async fn ping(&self) -> Result<()> {
-        self.get("ping", Query::none()).await?;
-        Ok(())
+    /// Sets the maximum number of idle connections kept open to the server.
+    /// Setting this to `0` disables re-using connections.
+    pub fn with_pool_max_idle_per_host(self, max: usize) -> Result<Client> {
+        let mut cli = self;
+        cli.http.pool_max_idle_per_host = Some(max);
+        cli.reqclient = cli.http.build()?;
+        Ok(cli)
     }
 
-    /// Get details about the software license. Note that access to the REST API
-    /// requires that the server has a valid license (after a 30-day trial
-    /// period). To get a license key you must upgrade to Subsonic Premium.
+    /// Sends requests through an existing HTTP client instead of one built
+    /// by `sunk`.
     ///
-    /// Forks of Subsonic (Libresonic, Airsonic, etc.) do not require licenses;
-    /// this method will always return a valid license and trial when attempting
-    /// to connect to these services.
-    pub async fn check_license(&self) -> Result<License> {
-        let res = self.get("getLicense", Query::none()).await?;
-        Ok(serde_json::from_value::<License>(res)?)
+    /// This shares the client's connection pool and settings with the rest of
+    /// an application. The pool settings above no longer apply; set them on
+    /// the given client instead.
+    ///
+    /// `sunk` follows redirects itself so that it can check where they lead,
+    /// so the given client should be built with [`redirect::Policy::none`].
+    ///
+    /// [`redirect::Policy::none`]: https://docs.rs/reqwest/0.11/reqwest/redirect/struct.Policy.html#method.none
+    pub fn with_http_client(self, client: ReqwestClient) -> Client {
+        let mut cli = self;
+        cli.reqclient = client.clone();
+        cli.http.shared = Some(client);
+        cli
     }
 
-    /// Initiates a rescan of the media libraries.
+    /// Pings the server and adjusts the target version to the highest version
+    /// supported by both the server and `sunk`.
     ///
-    /// # Note
+    /// Also detects the [server flavor](#method.server_flavor).
     ///
-    /// This method was introduced in version 1.15.0. It will not be supported
-    /// on servers with earlier versions of the Subsonic API.
-    pub async fn scan_library(&self) -> Result<()> {
-        self.get("startScan", Query::none()).await?;
-        Ok(())
+    /// If the target was set explicitly using [`with_target`], the override
+    /// wins and the target is left untouched. Returns the version the client
+    /// will target from now on.
+    ///
+    /// [`with_target`]: #method.with_target
+    pub async fn negotiate(&mut self) -> Result<Version> {
+        let res = self.get_response("ping", Query::none()).await?;
+        let server = res
+            .version()
+            .ok_or(Error::Other("server did not report a version"))?;
+        self.set_server_version(server);
+        self.flavor = Some(ServerFlavor::from_type(res.server_type()));
+        Ok(self.target_ver)
     }
 
-    /// Gets the status of a scan. Returns the current status for media library
-    /// scanning.
+    /// Returns the server software detected during the last [`negotiate`]
+    /// call, or `None` if the client hasn't negotiated yet.
     ///
-    /// # Note
+    /// Methods use the flavor to work around the server's known quirks.
     ///
-    /// This method was introduced in version 1.15.0. It will not be supported
-    /// on servers with earlier versions of the Subsonic API.
-    pub async fn scan_status(&self) -> Result<(bool, u64)> {
-        let res = self.get("getScanStatus", Query::none()).await?;
+    /// [`negotiate`]: #method.negotiate
+    pub fn server_flavor(&self) -> Option<ServerFlavor> {
+        self.flavor
+    }
 
-        #[derive(Deserialize)]
-        struct ScanStatus {
-            count: u64,
-            scanning: bool,
+    /// Returns the formats the server is likely to transcode streams to, most
+    /// widely supported first, for picking a [`StreamParams`] format.
+    ///
+    /// This is a heuristic that makes no requests: the API has no way to
+    /// list a server's transcoders, so the formats are those each server
+    /// supports out of the box, going by the [flavor] found by the last
+    /// [`negotiate`] call. Servers the client hasn't negotiated with are
+    /// assumed to be Subsonic. Servers before API version 1.6.0 can't be
+    /// asked for a format, so none are returned for them.
+    ///
+    /// [`StreamParams`]: ./struct.StreamParams.html
+    /// [flavor]: ./enum.ServerFlavor.html#method.transcode_formats
+    /// [`negotiate`]: #method.negotiate
+    pub fn transcode_formats(&self) -> Vec<StreamFormat> {
+        if self.server_ver.unwrap_or(self.ver) < "1.6.0".into() {
+            return Vec::new();
         }
-        let sc = serde_json::from_value::<ScanStatus>(res)?;
+        self.flavor
+            .unwrap_or(ServerFlavor::Subsonic)
+            .transcode_formats()
+            .to_vec()
+    }
 
-        Ok((sc.scanning, sc.count))
+    /// Detects the features the server supports.
+    ///
+    /// [Negotiates](#method.negotiate) the version with the server, then
+    /// asks for the OpenSubsonic extensions it supports. Servers that don't
+    /// implement OpenSubsonic are treated as having no extensions.
+    ///
+    /// If the client was given an [API key](#method.with_api_key) and the
+    /// server doesn't support them, the client switches to authenticating
+    /// with its user and password.
+    ///
+    /// The result is kept, and can be read again with
+    /// [`last_capabilities`](#method.last_capabilities).
+    pub async fn capabilities(&mut self) -> Result<Capabilities> {
+        // Extensions are public, so they can be checked before knowing which
+        // authentication works.
+        let extensions = match self.get("getOpenSubsonicExtensions", Query::none()).await {
+            Ok(res) => serde_json::from_value(res)?,
+            Err(Error::Api(..)) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        // Switch away from an unsupported API key before the ping, which
+        // would otherwise be rejected.
+        let api_keys = extensions
+            .iter()
+            .any(|e: &Extension| e.name == "apiKeyAuthentication");
+        if self.auth.api_key.is_some() && !api_keys {
+            warn!("Server doesn't support API keys; using the password instead");
+            self.auth.api_key = None;
+        }
+
+        self.negotiate().await?;
+        let server = self
+            .server_ver
+            .ok_or(Error::Other("server did not report a version"))?;
+
+        let capabilities = Capabilities::new(server, extensions);
+        self.capabilities = Some(capabilities.clone());
+        Ok(capabilities)
     }
 
-    /// Returns all configured top-level music folders.
-    pub async fn music_folders(&self) -> Result<Vec<MusicFolder>> {
-        #[allow(non_snake_case)]
-        let music_folder = self.get("getMusicFolders", Query::none()).await?;
+    /// Detects the server's version, flavor, and features again, replacing
+    /// what the client knew of them.
+    ///
+    /// Long-lived clients can call this after the server is upgraded to
+    /// pick up its new features without being recreated. Runs the same
+    /// detection as [`capabilities`](#method.capabilities).
+    pub async fn refresh(&mut self) -> Result<()> {
+        self.capabilities().await?;
+        Ok(())
+    }
 
-        Ok(get_list_as!(music_folder, MusicFolder))
+    /// Returns the features detected by the last call to
+    /// [`capabilities`](#method.capabilities) or [`refresh`](#method.refresh),
+    /// or `None` if neither has been called.
+    pub fn last_capabilities(&self) -> Option<&Capabilities> {
+        self.capabilities.as_ref()
     }
 
-    /// Returns all genres.
-    pub async fn genres(&self) -> Result<Vec<Genre>> {
-        let genre = self.get("getGenres", Query::none()).await?;
+    fn set_server_version(&mut self, server: Version) {
+        self.server_ver = Some(server);
+        if !self.pinned {
+            self.target_ver = ::std::cmp::min(self.ver, server);
+        }
+    }
 
-        Ok(get_list_as!(genre, Genre))
+    /// Internal helper function to construct a URL when the actual fetching is
+    /// not required.
+    #[allow(clippy::needless_pass_by_value)]
+    pub(crate) fn build_url(&self, query: &str, args: Query) -> Result<String> {
+        self.build_url_at(query, args, self.target_ver)
     }
 
-    /// Returns all currently playing media on the server.
-    pub async fn now_playing(&self) -> Result<Vec<NowPlaying>> {
-        let entry = self.get("getNowPlaying", Query::none()).await?;
-        Ok(get_list_as!(entry, NowPlaying))
+    /// Builds the URL for a request, claiming the given API version rather
+    /// than the client's target.
+    fn build_url_at(&self, query: &str, mut args: Query, ver: Version) -> Result<String> {
+        if let Some(ref id) = self.player_id {
+            if PLAYER_ENDPOINTS.contains(&query) {
+                args.arg("playerId", id.as_str());
+            }
+        }
+
+        let scheme = self.url.scheme();
+        let addr = self.url.host_str().ok_or(Error::Url(UrlError::Address))?;
+        let port = self.url.port().map(|p| format!(":{p}")).unwrap_or_default();
+        let path = self.url.path();
+
+        let mut url = [scheme, "://", addr, &port, path, "rest/"].concat();
+        url.push_str(query);
+        url.push('?');
+        url.push_str(&self.auth.to_url(ver));
+        if !args.is_empty() {
+            url.push('&');
+            url.push_str(&args.to_string());
+        }
+
+        Ok(url)
     }
 
-    /// Searches for lyrics matching the artist and title. Returns `None` if no
-    /// lyrics are found.
-    pub async fn lyrics<'a, S>(&self, artist: S, title: S) -> Result<Option<Lyrics>>
+    /// Returns an authenticated URL for streaming the media with the given ID.
+    ///
+    /// The URL can be handed to anything that will make the request itself,
+    /// such as a browser or an external media player. It carries everything
+    /// the server needs to authenticate it, and the server keeps no session
+    /// for it, so it works for as long as the user's password (or the
+    /// client's API key) is unchanged, however many times it is fetched.
+    ///
+    /// Anyone holding the URL can stream with the user's account, so treat
+    /// it as a credential: don't log it or share it beyond the player that
+    /// needs it.
+    pub fn stream_url_for(&self, id: &str, params: &StreamParams) -> Result<String> {
+        self.build_url("stream", params.to_query(id))
+    }
+
+    /// Returns an authenticated URL for downloading the original file of the
+    /// media with the given ID.
+    ///
+    /// Like [`stream_url_for`](#method.stream_url_for), the URL stands on
+    /// its own and should be treated as a credential.
+    pub fn download_url_for(&self, id: &str) -> Result<String> {
+        self.build_url("download", Query::with("id", id))
+    }
+
+    /// Returns an authenticated URL for the cover art with the given ID,
+    /// optionally scaled to `size` pixels.
+    pub fn cover_art_url_for<U>(&self, id: &str, size: U) -> Result<String>
     where
-        S: Into<Option<&'a str>>,
+        U: Into<Option<usize>>,
     {
-        let args = Query::with("artist", artist.into())
-            .arg("title", title.into())
-            .build();
-        let res = self.get("getLyrics", args).await?;
+        let query = Query::with("id", id).arg("size", size.into()).build();
+        self.build_url("getCoverArt", query)
+    }
 
-        if res.get("value").is_some() {
-            Ok(Some(serde_json::from_value(res)?))
-        } else {
-            Ok(None)
-        }
+    /// Fetches the cover art with the given ID as a `data:` URI, optionally
+    /// scaled to `size` pixels.
+    ///
+    /// The URI embeds the image itself, so it can be put straight into HTML
+    /// or SVG without another request. The image's MIME type is taken from
+    /// the response, or guessed from the image if the server didn't send
+    /// one.
+    pub async fn cover_art_data_uri<U>(&self, id: &str, size: U) -> Result<String>
+    where
+        U: Into<Option<usize>>,
+    {
+        let query = Query::with("id", id).arg("size", size.into()).build();
+        let art = self.get_binary("getCoverArt", query).await?;
+        let mime = art
+            .content_type
+            .filter(|t| t.starts_with("image/"))
+            .unwrap_or_else(|| sniff_image_type(&art.data).to_string());
+        Ok(data_uri(&mime, &art.data))
     }
 
-    /// Returns albums, artists and songs matching the given search criteria.
-    /// Supports paging through the result. See the [search module] for
-    /// documentation.
+    /// Issues a request to the Subsonic server.
     ///
-    /// [search module]: ./search/index.html
+    /// A query should be one documented in the [official API].
     ///
-    /// # Examples
+    /// [official API]: http://www.subsonic.org/pages/api.jsp
     ///
-    /// Basic usage:
+    /// # Errors
+    ///
+    /// Will return an error if any of the following occurs:
+    ///
+    /// - server is built with an incomplete URL
+    /// - connecting to the server fails
+    /// - the server returns an API error
+    pub(crate) async fn get(&self, query: &str, args: Query) -> Result<serde_json::Value> {
+        let response = self.get_response(query, args).await?;
+        Ok(match response.into_value() {
+            Some(v) => v,
+            None => serde_json::Value::Null,
+        })
+    }
+
+    /// Fetches a response and parses the items at `path` one at a time,
+    /// handing each to `f`. See `response::parse_items`.
+    ///
+    /// The body is read in full first; only the intermediate JSON tree is
+    /// avoided.
+    pub(crate) async fn get_items<T, F>(
+        &self,
+        query: &str,
+        args: Query,
+        path: &[&str],
+        f: F,
+    ) -> Result<()>
+    where
+        T: DeserializeOwned,
+        F: FnMut(T),
+    {
+        let uri: Url = self.build_url(query, args)?.parse().unwrap();
+        let res = self.send(uri).await?;
+        if !res.status().is_success() {
+            return Err(self.status_error(&res));
+        }
+
+        let body = self.text(res).await?;
+        response::parse_items(&body, self.auth.xml, path, f).map_err(|e| self.failed(e))
+    }
+
+    /// Fetches a single artist, album, or song by its ID, using the entity
+    /// cache if the client has one.
+    pub(crate) async fn get_entity(&self, query: &str, id: &str) -> Result<serde_json::Value> {
+        if let Some(value) = self.cache.as_ref().and_then(|c| c.get(query, id)) {
+            return Ok(value);
+        }
+
+        let value = self.get(query, Query::with("id", id)).await?;
+        if let Some(ref cache) = self.cache {
+            cache.insert(query, id, value.clone());
+        }
+        Ok(value)
+    }
+
+    /// Drops anything cached for the entity with the ID, after it has been
+    /// changed.
+    pub(crate) fn invalidate(&self, id: &str) {
+        if let Some(ref cache) = self.cache {
+            cache.invalidate(id);
+        }
+    }
+
+    /// Issues a request to any endpoint of the Subsonic server, with extra
+    /// key-value pairs added to the arguments.
+    ///
+    /// This allows calling endpoints and passing parameters that `sunk`
+    /// doesn't model yet, such as those added by forks of Subsonic. Returns
+    /// the contents of the response, or `Null` if it had none.
+    ///
+    /// # Errors
+    ///
+    /// Has the same error conditions as the endpoints that `sunk` models.
+    pub async fn get_with_extra(
+        &self,
+        query: &str,
+        args: Query,
+        extra: &[(&str, &str)],
+    ) -> Result<serde_json::Value> {
+        let mut args = args;
+        args.extra(extra);
+        self.get(query, args).await
+    }
+
+    /// Issues a request to any endpoint of the Subsonic server, claiming the
+    /// given API version for this request only.
+    ///
+    /// The client's target version is left as it is. This is meant for
+    /// diagnostics, such as finding the earliest version at which a server
+    /// honours an endpoint; other methods always use the target version.
+    /// Returns the contents of the response, or `Null` if it had none.
+    ///
+    /// # Errors
+    ///
+    /// Has the same error conditions as the endpoints that `sunk` models.
+    /// Servers typically answer a version they don't support with
+    /// [`ApiError::ServerMustUpgrade`] or [`ApiError::ClientMustUpgrade`].
+    ///
+    /// [`ApiError::ServerMustUpgrade`]: ./enum.ApiError.html#variant.ServerMustUpgrade
+    /// [`ApiError::ClientMustUpgrade`]: ./enum.ApiError.html#variant.ClientMustUpgrade
+    pub async fn get_at_version(
+        &self,
+        ver: Version,
+        query: &str,
+        args: Query,
+    ) -> Result<serde_json::Value> {
+        let response = self.get_response_at(query, args, ver).await?;
+        Ok(response.into_value().unwrap_or(serde_json::Value::Null))
+    }
+
+    /// Issues a request to the Subsonic server, returning the whole response
+    /// envelope rather than just its contents.
+    ///
+    /// # Errors
+    ///
+    /// Has the same error conditions as [`get`](#method.get).
+    pub(crate) async fn get_response(&self, query: &str, args: Query) -> Result<Response> {
+        self.get_response_at(query, args, self.target_ver).await
+    }
+
+    /// Issues a request like [`get_response`](#method.get_response), claiming
+    /// the given API version.
+    async fn get_response_at(&self, query: &str, args: Query, ver: Version) -> Result<Response> {
+        let uri: Url = self.build_url_at(query, args, ver)?.parse().unwrap();
+        let res = self.send(uri).await?;
+
+        if res.status().is_success() {
+            let status = res.status();
+            let html = is_html(content_type(&res));
+            let body = self.text(res).await?;
+            if html || looks_like_html(&body, self.auth.xml) {
+                return Err(self.failed(Error::Html(status)));
+            }
+            let response = if self.auth.xml {
+                Response::parse_xml(&body)
+            } else {
+                Response::parse(&body)
+            }
+            .map_err(|e| self.failed(e))?;
+            self.checked(response)
+        } else {
+            Err(self.status_error(&res))
+        }
+    }
+
+    /// Returns the error for a response with an unsuccessful status, counting
+    /// it in the metrics.
+    fn status_error(&self, res: &reqwest::Response) -> Error {
+        let status = res.status();
+        self.failed(if is_html(content_type(res)) {
+            Error::Html(status)
+        } else {
+            Error::Connection(status)
+        })
+    }
+
+    /// Turns a failed response into its error.
+    fn checked(&self, response: Response) -> Result<Response> {
+        if response.is_ok() {
+            return Ok(response);
+        }
+        let help_url = response.help_url().map(str::to_string);
+        Err(self.failed(
+            response
+                .into_error()
+                .map(|e| Error::Api(e, help_url))
+                .unwrap_or(Error::Other("unable to retrieve error")),
+        ))
+    }
+
+    /// Counts an error in the client's metrics, and passes it on.
+    fn failed(&self, err: Error) -> Error {
+        self.metrics.error(&err);
+        err
+    }
+
+    /// Reads a whole response body as text, counting it in the metrics.
+    async fn text(&self, res: reqwest::Response) -> Result<String> {
+        let body = res.text().await.map_err(|e| self.failed(e.into()))?;
+        self.metrics.downloaded(body.len());
+        Ok(body)
+    }
+
+    /// Reads a whole response body, counting it in the metrics.
+    async fn bytes(&self, res: reqwest::Response) -> Result<Vec<u8>> {
+        let body = res.bytes().await.map_err(|e| self.failed(e.into()))?;
+        self.metrics.downloaded(body.len());
+        Ok(body.to_vec())
+    }
+
+    /// Fetches an unprocessed response from the server rather than a JSON- or
+    /// XML-parsed one.
+    pub(crate) async fn get_raw(&self, query: &str, args: Query) -> Result<String> {
+        let uri: Url = self.build_url(query, args)?.parse().unwrap();
+        let res = self.send(uri).await?;
+        self.text(res).await
+    }
+
+    /// Returns a response as a vector of bytes rather than serialising it.
+    pub(crate) async fn get_bytes(&self, query: &str, args: Query) -> Result<Vec<u8>> {
+        Ok(self.get_binary(query, args).await?.data)
+    }
+
+    /// Fetches the body of any endpoint as bytes, along with its MIME type.
+    ///
+    /// This is the building block for endpoints that return files rather than
+    /// JSON or XML, such as `getCoverArt`, `getAvatar`, `getCaptions`,
+    /// `stream`, and `download`, for anything `sunk` doesn't wrap yet. The
+    /// body is passed on as is, without assuming it is text.
+    ///
+    /// # Errors
+    ///
+    /// Servers report failures of these endpoints with an ordinary JSON or
+    /// XML response instead of the file; these are returned as errors, as
+    /// are HTTP errors.
+    pub async fn get_binary(&self, endpoint: &str, args: Query) -> Result<BinaryResponse> {
+        let uri: Url = self.build_url(endpoint, args)?.parse().unwrap();
+        let res = self.send(uri).await?;
+        if !res.status().is_success() {
+            return Err(self.status_error(&res));
+        }
+
+        let content_type = content_type(&res).map(str::to_string);
+        let data = self.bytes(res).await?;
+
+        if let Some(response) = content_type.as_deref().and_then(|t| api_response(t, &data)) {
+            self.checked(response)?;
+        }
+        Ok(BinaryResponse { data, content_type })
+    }
+
+    /// Returns a response as a stream of chunks, as they are received.
+    pub(crate) async fn get_stream(&self, query: &str, args: Query) -> Result<MediaStream> {
+        let uri: Url = self.build_url(query, args)?.parse().unwrap();
+        let res = self.send(uri).await?;
+        if !res.status().is_success() {
+            return Err(self.status_error(&res));
+        }
+        self.media_stream(res).await
+    }
+
+    /// Returns a response as a stream of chunks, asking the server to start
+    /// from the given byte. Also returns whether the server honoured the
+    /// request; if not, the stream starts from the beginning.
+    pub(crate) async fn get_stream_from(
+        &self,
+        query: &str,
+        args: Query,
+        from_byte: u64,
+    ) -> Result<(bool, MediaStream)> {
+        let uri: Url = self.build_url(query, args)?.parse().unwrap();
+        let mut headers = HeaderMap::new();
+        let range = format!("bytes={from_byte}-");
+        headers.insert(RANGE, range.parse().expect("range header is ASCII"));
+
+        let res = self.send_with(uri, headers).await?;
+        let partial = match res.status() {
+            StatusCode::PARTIAL_CONTENT => true,
+            s if s.is_success() => false,
+            _ => return Err(self.status_error(&res)),
+        };
+        Ok((partial, self.media_stream(res).await?))
+    }
+
+    /// Wraps a media response in a stream, unless it is a Subsonic response
+    /// reporting an error instead. Such a response is small, so its body is
+    /// read up front to check it.
+    async fn media_stream(&self, res: reqwest::Response) -> Result<MediaStream> {
+        let content_type = match content_type(&res) {
+            Some(t) if is_api_type(t) => t.to_string(),
+            _ => return Ok(MediaStream::new(res, self.metrics.clone())),
+        };
+        let data = self.bytes(res).await?;
+        if let Some(response) = api_response(&content_type, &data) {
+            self.checked(response)?;
+        }
+        Ok(MediaStream::from_bytes(data))
+    }
+
+    /// Returns the raw bytes of a HLS slice.
+    pub async fn hls_bytes(&self, hls: &Hls) -> Result<Vec<u8>> {
+        let url: Url = self
+            .url
+            .join(&hls.url)
+            .map_err(<url::ParseError as Into<UrlError>>::into)?;
+        let res = self.send(url).await?;
+        self.bytes(res).await
+    }
+
+    /// Fetches a resource that is not hosted by the Subsonic server, such as
+    /// an image from last.fm.
+    ///
+    /// The request shares the client's connection pool, but is never given
+    /// the authentication parameters.
+    pub(crate) async fn get_external(&self, url: &str) -> Result<Vec<u8>> {
+        let url = url
+            .parse::<Url>()
+            .map_err(<url::ParseError as Into<UrlError>>::into)?;
+        let res = self.send(url).await?;
+        if !res.status().is_success() {
+            return Err(self.status_error(&res));
+        }
+        self.bytes(res).await
+    }
+
+    /// Checks whether a resource not hosted by the Subsonic server responds
+    /// successfully, without reading its body.
+    ///
+    /// Like [`get_external`](#method.get_external), the request is never
+    /// given the authentication parameters. Failing to connect, or getting no
+    /// response within five seconds, counts as not responding.
+    pub(crate) async fn probe_external(&self, url: &str) -> Result<bool> {
+        let url = url
+            .parse::<Url>()
+            .map_err(<url::ParseError as Into<UrlError>>::into)?;
+        match tokio::time::timeout(PROBE_TIMEOUT, self.send(url)).await {
+            Ok(Ok(res)) => Ok(res.status().is_success()),
+            Ok(Err(_)) | Err(_) => Ok(false),
+        }
+    }
+
+    /// Sends a GET request, following up to five redirects.
+    ///
+    /// If a redirect drops the query string, the original one (containing
+    /// the authentication parameters) is attached to the new location.
+    ///
+    /// # Errors
+    ///
+    /// Aside from connection errors, the method will error if the server
+    /// redirects too many times, or redirects back to a URL it already
+    /// redirected from.
+    async fn send(&self, url: Url) -> Result<reqwest::Response> {
+        self.send_with(url, HeaderMap::new()).await
+    }
+
+    /// Sends a GET request with extra headers, following redirects like
+    /// [`send`](#method.send). The headers are sent again on each redirect.
+    async fn send_with(&self, url: Url, headers: HeaderMap) -> Result<reqwest::Response> {
+        info!("Connecting to {}", redacted(&url));
+
+        let mut url = url;
+        let mut visited = Vec::new();
+        loop {
+            let start = Instant::now();
+            let res = self
+                .reqclient
+                .get(url.clone())
+                .headers(headers.clone())
+                .send()
+                .await;
+            self.metrics.request(start.elapsed());
+            let res = res.map_err(|e| self.failed(e.into()))?;
+            match res.status() {
+                StatusCode::MOVED_PERMANENTLY
+                | StatusCode::FOUND
+                | StatusCode::SEE_OTHER
+                | StatusCode::TEMPORARY_REDIRECT
+                | StatusCode::PERMANENT_REDIRECT => {}
+                _ => return Ok(res),
+            }
+
+            let location = res
+                .headers()
+                .get(LOCATION)
+                .and_then(|l| l.to_str().ok())
+                .ok_or_else(|| self.failed(Error::Other("redirect is missing a location")))?;
+            let next = redirect_target(&url, location).map_err(|e| self.failed(e))?;
+            debug!("Redirected to {}", next.path());
+
+            visited.push(url);
+            if visited.contains(&next) {
+                return Err(self.failed(Error::Other("redirect loop detected")));
+            }
+            if visited.len() > MAX_REDIRECTS {
+                return Err(self.failed(Error::Other("too many redirects")));
+            }
+            url = next;
+        }
+    }
+
+    /// Tests a connection with the server.
+    ///
+    /// Only needs a shared reference, so health checks can run from several
+    /// tasks at once against one client.
+    pub async fn ping(&self) -> Result<()> {
+        self.get("ping", Query::none()).await?;
+        Ok(())
+    }
+
+    /// Checks whether the server accepts the client's username and password.
+    ///
+    /// Returns `Ok(false)` if the server rejects the credentials, rather than
+    /// an error; any error returned means the check itself couldn't be made,
+    /// such as when the server can't be reached.
+    pub async fn verify_credentials(&self) -> Result<bool> {
+        accepted(self.ping().await)
+    }
+
+    /// Returns the account that the client's [API key](#method.with_api_key)
+    /// belongs to.
+    ///
+    /// Useful for confirming which account a stored key maps to.
+    ///
+    /// # Errors
+    ///
+    /// Aside from the other errors the `Client` may cause, the method will
+    /// return [`Error::UnsupportedApi`] if the server doesn't support API
+    /// keys, or the client isn't using one.
+    ///
+    /// [`Error::UnsupportedApi`]: ./enum.Error.html#variant.UnsupportedApi
+    pub async fn token_info(&mut self) -> Result<TokenInfo> {
+        let supported = match self.last_capabilities() {
+            Some(capabilities) => capabilities.supports_api_key_auth(),
+            None => self.capabilities().await?.supports_api_key_auth(),
+        };
+        if !supported || self.auth.api_key.is_none() {
+            return Err(Error::UnsupportedApi("apiKeyAuthentication"));
+        }
+
+        let res = self.get("tokenInfo", Query::none()).await?;
+        Ok(serde_json::from_value(res)?)
+    }
+
+    /// Get details about the software license. Note that access to the REST API
+    /// requires that the server has a valid license (after a 30-day trial
+    /// period). To get a license key you must upgrade to Subsonic Premium.
+    ///
+    /// Forks of Subsonic (Libresonic, Airsonic, etc.) do not require licenses;
+    /// this method will always return a valid license and trial when attempting
+    /// to connect to these services.
+    ///
+    /// Once the trial has expired, every other request fails with an error
+    /// for which [`Error::is_trial_expired`] is true.
+    ///
+    /// [`Error::is_trial_expired`]: ./enum.Error.html#method.is_trial_expired
+    pub async fn check_license(&self) -> Result<License> {
+        let res = self.get("getLicense", Query::none()).await?;
+        Ok(serde_json::from_value::<License>(res)?)
+    }
+
+    /// Initiates a rescan of the media libraries.
+    ///
+    /// # Note
+    ///
+    /// This method was introduced in version 1.15.0. It will not be supported
+    /// on servers with earlier versions of the Subsonic API.
+    pub async fn scan_library(&self) -> Result<()> {
+        self.get("startScan", Query::none()).await?;
+        Ok(())
+    }
+
+    /// Asks the server to check its podcasts for new episodes.
+    ///
+    /// The server checks in the background; see
+    /// [`wait_for_podcast_refresh`](#method.wait_for_podcast_refresh) to be
+    /// told when new episodes arrive.
+    pub async fn refresh_podcasts(&self) -> Result<()> {
+        self.get("refreshPodcasts", Query::none()).await?;
+        Ok(())
+    }
+
+    /// Refreshes the server's podcasts and waits up to `timeout` for new
+    /// episodes to arrive.
+    ///
+    /// Returns the episodes published after the newest one the server knew
+    /// of before refreshing, or an empty list if none arrived in time.
+    ///
+    /// The wait is timed with `tokio`, so the future must be run within a
+    /// `tokio` runtime.
+    pub async fn wait_for_podcast_refresh(&self, timeout: Duration) -> Result<Vec<Episode>> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let before = Episode::newest(self, None::<usize>).await?;
+        let since = before.iter().map(|e| e.publish_date.clone()).max();
+        self.refresh_podcasts().await?;
+
+        loop {
+            let now = tokio::time::Instant::now();
+            if now >= deadline {
+                return Ok(Vec::new());
+            }
+            tokio::time::sleep(PODCAST_POLL_INTERVAL.min(deadline - now)).await;
+
+            let episodes = Episode::newest(self, None::<usize>).await?;
+            let added = published_after(episodes, since.as_deref());
+            if !added.is_empty() {
+                return Ok(added);
+            }
+        }
+    }
+
+    /// Gets the status of a scan. Returns the current status for media library
+    /// scanning.
+    ///
+    /// # Note
+    ///
+    /// This method was introduced in version 1.15.0. It will not be supported
+    /// on servers with earlier versions of the Subsonic API.
+    pub async fn scan_status(&self) -> Result<(bool, u64)> {
+        let res = self.get("getScanStatus", Query::none()).await?;
+
+        #[derive(Deserialize)]
+        struct ScanStatus {
+            count: u64,
+            scanning: bool,
+        }
+        let sc = serde_json::from_value::<ScanStatus>(res)?;
+
+        Ok((sc.scanning, sc.count))
+    }
+
+    /// Returns all configured top-level music folders.
+    pub async fn music_folders(&self) -> Result<Vec<MusicFolder>> {
+        #[allow(non_snake_case)]
+        let music_folder = self.get("getMusicFolders", Query::none()).await?;
+
+        Ok(get_list_as!(music_folder, MusicFolder))
+    }
+
+    /// Returns a view of the client limited to the music folder with the ID.
+    ///
+    /// The view's browsing, listing, and search methods pass the folder along
+    /// with each request.
+    pub fn scoped_to_folder(&self, folder_id: usize) -> FolderScope<'_> {
+        FolderScope::new(self, folder_id)
+    }
+
+    /// Returns the folder-based index of artists, optionally limited to those
+    /// in one music folder.
+    pub async fn indexes<U>(&self, folder_id: U) -> Result<Indexes>
+    where
+        U: Into<Option<usize>>,
+    {
+        let args = Query::with("musicFolderId", folder_id.into());
+        let res = self.get("getIndexes", args).await?;
+        Ok(serde_json::from_value(res)?)
+    }
+
+    /// Calls `f` with each artist in the folder-based index, optionally
+    /// limited to those in one music folder.
+    ///
+    /// Unlike [`indexes`](#method.indexes), the artists are parsed from the
+    /// body one at a time, without first building the whole index as a JSON
+    /// tree. The body itself is read in full before the first artist is
+    /// parsed, so the memory saved is that tree's, not the body's: peak
+    /// memory is the raw body plus whatever `f` keeps. XML responses are
+    /// converted to a JSON tree first, so they save nothing.
+    pub async fn for_each_index_artist<U, F>(&self, folder_id: U, f: F) -> Result<()>
+    where
+        U: Into<Option<usize>>,
+        F: FnMut(IndexArtist),
+    {
+        let args = Query::with("musicFolderId", folder_id.into());
+        self.get_items("getIndexes", args, &["indexes", "index", "artist"], f)
+            .await
+    }
+
+    /// Calls `f` with each artist organised by ID3 tags, optionally limited
+    /// to those in one music folder.
+    ///
+    /// As with [`for_each_index_artist`](#method.for_each_index_artist), the
+    /// artists are parsed one at a time from the fully read body, rather than
+    /// building the whole response as a JSON tree.
+    pub async fn for_each_artist<U, F>(&self, folder_id: U, f: F) -> Result<()>
+    where
+        U: Into<Option<usize>>,
+        F: FnMut(Artist),
+    {
+        let args = Query::with("musicFolderId", folder_id.into());
+        self.get_items("getArtists", args, &["artists", "index", "artist"], f)
+            .await
+    }
+
+    /// Returns the artists organised by ID3 tags in alphabetical groups,
+    /// such as for an A to Z scroll bar, optionally limited to those in one
+    /// music folder.
+    ///
+    /// Each group is labelled with a letter, and holds the artists whose
+    /// names start with it once any of the server's ignored articles (such
+    /// as "The") are skipped. Artists whose names start with anything else
+    /// are grouped under `#`, which comes first. Artists are grouped by the
+    /// client rather than as the server lists them, as servers disagree on
+    /// how to group names with articles or accents.
+    pub async fn artist_index<U>(&self, folder_id: U) -> Result<Vec<(String, Vec<Artist>)>>
+    where
+        U: Into<Option<usize>>,
+    {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct _Artists {
+            #[serde(default)]
+            ignored_articles: String,
+            #[serde(default)]
+            index: Vec<_Index>,
+        }
+
+        #[derive(Deserialize)]
+        struct _Index {
+            #[serde(default)]
+            artist: Vec<Artist>,
+        }
+
+        let args = Query::with("musicFolderId", folder_id.into());
+        let res = self.get("getArtists", args).await?;
+        let raw = serde_json::from_value::<_Artists>(res)?;
+        let articles = raw
+            .ignored_articles
+            .split_whitespace()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        let artists = raw.index.into_iter().flat_map(|i| i.artist).collect();
+        Ok(collections::artist::bucket_artists(artists, &articles))
+    }
+
+    /// Returns the folder-based index of artists if it has changed since the
+    /// given time, in milliseconds since the Unix epoch.
+    ///
+    /// The server leaves the index out of its response if nothing has
+    /// changed, making this cheap to poll. Pass the [`last_modified`] of the
+    /// previous result to check for changes since then.
+    ///
+    /// [`last_modified`]: ./struct.Indexes.html#structfield.last_modified
+    pub async fn indexes_if_modified<U>(&self, since: u64, folder_id: U) -> Result<Option<Indexes>>
+    where
+        U: Into<Option<usize>>,
+    {
+        let args = Query::with("ifModifiedSince", since)
+            .arg("musicFolderId", folder_id.into())
+            .build();
+        let res = self.get("getIndexes", args).await?;
+        collections::indexes_if_modified(res)
+    }
+
+    /// Returns all genres, optionally sorted.
+    ///
+    /// Genres whose names differ only in case are merged into one, keeping
+    /// the spelling with the most songs.
+    pub async fn genres<S>(&self, sort: S) -> Result<Vec<Genre>>
+    where
+        S: Into<Option<GenreSort>>,
+    {
+        let genre = self.get("getGenres", Query::none()).await?;
+
+        Ok(collections::tidy_genres(
+            get_list_as!(genre, Genre),
+            sort.into(),
+        ))
+    }
+
+    /// Returns the server's genres, keyed by their lowercased names.
+    ///
+    /// Each value is the spelling the server uses, which endpoints such as
+    /// `getSongsByGenre` need exactly. Genres whose names differ only in case
+    /// are keyed once, by the spelling that has the most songs of its own,
+    /// as in [`genres`]. Endpoints that take a genre still accept the other
+    /// spellings as given. The map is fetched on first use and kept; call
+    /// [`refresh_genre_map`] after the library changes.
+    ///
+    /// [`genres`]: #method.genres
+    /// [`refresh_genre_map`]: #method.refresh_genre_map
+    pub async fn genre_map(&self) -> Result<HashMap<String, String>> {
+        if let Some(ref names) = *self.genre_names.lock().unwrap() {
+            return Ok(names.canonical.clone());
+        }
+        self.refresh_genre_map().await
+    }
+
+    /// Fetches the server's genres again, replacing the map kept by
+    /// [`genre_map`].
+    ///
+    /// [`genre_map`]: #method.genre_map
+    pub async fn refresh_genre_map(&self) -> Result<HashMap<String, String>> {
+        let genre = self.get("getGenres", Query::none()).await?;
+        let names = GenreNames::new(get_list_as!(genre, Genre));
+        let canonical = names.canonical.clone();
+        *self.genre_names.lock().unwrap() = Some(names);
+        Ok(canonical)
+    }
+
+    /// Returns the server's spelling of the genre, ignoring case. Genres the
+    /// server doesn't know are returned as given, as are all genres if the
+    /// server's can't be fetched.
+    pub(crate) async fn resolve_genre(&self, genre: &str) -> String {
+        if let Some(name) = self.cached_genre(genre) {
+            return name;
+        }
+        match self.refresh_genre_map().await {
+            Ok(_) => self.cached_genre(genre),
+            Err(_) => None,
+        }
+        .unwrap_or_else(|| genre.to_string())
+    }
+
+    /// Returns whether the server lists albums in several genres at once, if
+    /// [`Album::list_by_genres`] has found out.
+    ///
+    /// [`Album::list_by_genres`]: ./struct.Album.html#method.list_by_genres
+    pub(crate) fn multi_genre(&self) -> Option<bool> {
+        *self.multi_genre.lock().unwrap()
+    }
+
+    pub(crate) fn set_multi_genre(&self, supported: bool) {
+        *self.multi_genre.lock().unwrap() = Some(supported);
+    }
+
+    /// Resolves a genre from the kept genre map, if it has been fetched.
+    fn cached_genre(&self, genre: &str) -> Option<String> {
+        let names = self.genre_names.lock().unwrap();
+        names.as_ref().map(|names| names.resolve(genre))
+    }
+
+    /// Returns all currently playing media on the server.
+    pub async fn now_playing(&self) -> Result<Vec<NowPlaying>> {
+        let entry = self.get("getNowPlaying", Query::none()).await?;
+        Ok(get_list_as!(entry, NowPlaying))
+    }
+
+    /// Fetches the songs with the given IDs, such as those of a saved play
+    /// queue.
+    ///
+    /// The API has no way to fetch several songs at once, so each song is
+    /// requested on its own, with up to eight requests in flight at a time.
+    /// The results are in the same order as the IDs, and a song that fails
+    /// to fetch doesn't stop the others.
+    pub async fn get_songs(&self, ids: &[&str]) -> Vec<Result<Song>> {
+        stream::iter(ids)
+            .map(|id| Song::get(self, id))
+            .buffered(SONG_FETCH_CONCURRENCY)
+            .collect()
+            .await
+    }
+
+    /// Fetches a song along with its album and artist, such as for a "now
+    /// playing" view.
+    ///
+    /// The album and artist are fetched at the same time once the song has
+    /// arrived. Either is `None` if the song doesn't name it, or the server
+    /// can't find it; other errors fail the whole call.
+    pub async fn get_song_full(&self, id: &str) -> Result<(Song, Option<Album>, Option<Artist>)> {
+        let song = Song::get(self, id).await?;
+        let album = async {
+            match song.album_id {
+                Some(ref id) => found(Album::get(self, id).await),
+                None => Ok(None),
+            }
+        };
+        let artist = async {
+            match song.artist_id {
+                Some(ref id) => found(Artist::get(self, id).await),
+                None => Ok(None),
+            }
+        };
+        let (album, artist) = future::join(album, artist).await;
+        Ok((song, album?, artist?))
+    }
+
+    /// Searches for lyrics matching the artist and title. Returns `None` if no
+    /// lyrics are found.
+    pub async fn lyrics<'a, S>(&self, artist: S, title: S) -> Result<Option<Lyrics>>
+    where
+        S: Into<Option<&'a str>>,
+    {
+        let args = Query::with("artist", artist.into())
+            .arg("title", title.into())
+            .build();
+        let res = self.get("getLyrics", args).await?;
+
+        if res.get("value").is_some() {
+            Ok(Some(serde_json::from_value(res)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns albums, artists and songs matching the given search criteria.
+    /// Supports paging through the result. See the [search module] for
+    /// documentation.
+    ///
+    /// [search module]: ./search/index.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
     ///
     /// ```no_run
     /// use sunk::search::{self, SearchPage};
@@ -341,6 +1635,25 @@ impl Client {
         artist_page: SearchPage,
         album_page: SearchPage,
         song_page: SearchPage,
+    ) -> Result<SearchResult> {
+        self.search_in_folders(query, artist_page, album_page, song_page, &[])
+            .await
+    }
+
+    /// Searches like [`search`], but only in the given music folders.
+    ///
+    /// The folder IDs are sent as repeated `musicFolderId` parameters, which
+    /// some OpenSubsonic servers accept. Other servers only honour the
+    /// first folder.
+    ///
+    /// [`search`]: #method.search
+    pub async fn search_in_folders(
+        &self,
+        query: &str,
+        artist_page: SearchPage,
+        album_page: SearchPage,
+        song_page: SearchPage,
+        folders: &[usize],
     ) -> Result<SearchResult> {
         // FIXME There has to be a way to make this nicer.
         let args = Query::with("query", query)
@@ -350,37 +1663,585 @@ impl Client {
             .arg("albumOffset", album_page.offset)
             .arg("songCount", song_page.count)
             .arg("songOffset", song_page.offset)
+            .arg_list("musicFolderId", folders)
             .build();
 
-        let res = self.get("search3", args).await?;
-        Ok(serde_json::from_value::<SearchResult>(res)?)
+        let res = self.get("search3", args).await?;
+        Ok(serde_json::from_value::<SearchResult>(res)?)
+    }
+
+    /// Counts the albums, artists and songs matching the given search query.
+    ///
+    /// The Subsonic API doesn't report how many results a search matches, so
+    /// the search is paged through until each kind of result runs out. To
+    /// keep this cheap, counting stops after 1000 results of any one kind;
+    /// the returned counts are marked as `capped` if this happens.
+    pub async fn search_count_estimate(&self, query: &str) -> Result<SearchCounts> {
+        let mut artists = Tally::default();
+        let mut albums = Tally::default();
+        let mut songs = Tally::default();
+        let mut capped = false;
+        let mut offset = 0;
+
+        while !(artists.done && albums.done && songs.done) {
+            let args = Query::with("query", query)
+                .arg("artistCount", artists.next_count(COUNT_PAGE_SIZE))
+                .arg("artistOffset", offset)
+                .arg("albumCount", albums.next_count(COUNT_PAGE_SIZE))
+                .arg("albumOffset", offset)
+                .arg("songCount", songs.next_count(COUNT_PAGE_SIZE))
+                .arg("songOffset", offset)
+                .build();
+            let res = self.get("search3", args).await?;
+            let page = serde_json::from_value::<SearchResult>(res)?;
+
+            for (tally, found) in [
+                (&mut artists, page.artists.len()),
+                (&mut albums, page.albums.len()),
+                (&mut songs, page.songs.len()),
+            ] {
+                if !tally.done {
+                    capped |= tally.record(found, COUNT_PAGE_SIZE, COUNT_CAP);
+                }
+            }
+            offset += COUNT_PAGE_SIZE;
+        }
+
+        Ok(SearchCounts {
+            artists: artists.total,
+            albums: albums.total,
+            songs: songs.total,
+            capped,
+        })
+    }
+
+    /// Searches for albums, artists and songs, yielding each result as soon
+    /// as the page holding it arrives.
+    ///
+    /// Results are requested 20 of each kind at a time, or the client's
+    /// [default page size] if one is set. Within a page the kinds are
+    /// interleaved, so an artist, an album and a song are yielded in turn
+    /// until one kind runs out. A kind stops being requested once it returns
+    /// a short page, and the stream ends when all three have. The stream
+    /// also ends after yielding an error.
+    ///
+    /// Pages can overlap when the library changes during the search, so
+    /// each result is only yielded the first time it is seen.
+    ///
+    /// [default page size]: #method.with_default_page_size
+    pub fn search_stream<'a>(
+        &'a self,
+        query: &'a str,
+    ) -> impl Stream<Item = Result<SearchHit>> + 'a {
+        let size = self.page_size(None).unwrap_or(STREAM_PAGE_SIZE);
+        stream::unfold(PagedSearch::default(), move |mut search| async move {
+            loop {
+                if let Some(hit) = search.pop() {
+                    return Some((Ok(hit), search));
+                }
+                if search.done() {
+                    return None;
+                }
+
+                let [artists, albums, songs] = search.next_counts(size);
+                let args = Query::with("query", query)
+                    .arg("artistCount", artists)
+                    .arg("artistOffset", search.offset)
+                    .arg("albumCount", albums)
+                    .arg("albumOffset", search.offset)
+                    .arg("songCount", songs)
+                    .arg("songOffset", search.offset)
+                    .build();
+                let page = match self.get("search3", args).await {
+                    Ok(res) => serde_json::from_value::<SearchResult>(res).map_err(Error::from),
+                    Err(e) => Err(e),
+                };
+                match page {
+                    Ok(page) => search.push(page, size),
+                    Err(e) => {
+                        search.stop();
+                        return Some((Err(e), search));
+                    }
+                }
+            }
+        })
+    }
+
+    /// Returns every artist, album and song matching the given search query.
+    ///
+    /// The search is paged through until each kind of result returns a short
+    /// page, or until `max` results of that kind have been collected. Without
+    /// a cap a broad query can fetch the whole library, so one should usually
+    /// be given. Results that show up on more than one page are only kept
+    /// once, and a kind of result stops being paged through once a page adds
+    /// none.
+    pub async fn search_all<U>(
+        &self,
+        query: &str,
+        max: U,
+    ) -> Result<(Vec<Artist>, Vec<Album>, Vec<Song>)>
+    where
+        U: Into<Option<usize>>,
+    {
+        let cap = max.into().unwrap_or(usize::MAX);
+        let size = cap.min(ALL_PAGE_SIZE);
+        let (mut artists, mut albums, mut songs) = (Vec::new(), Vec::new(), Vec::new());
+        let mut seen = HashSet::new();
+        let mut tallies = [Tally::default(), Tally::default(), Tally::default()];
+        let mut offset = 0;
+
+        if cap == 0 {
+            tallies.iter_mut().for_each(|t| t.done = true);
+        }
+        while !tallies.iter().all(|t| t.done) {
+            let args = Query::with("query", query)
+                .arg("artistCount", tallies[0].next_count(size))
+                .arg("artistOffset", offset)
+                .arg("albumCount", tallies[1].next_count(size))
+                .arg("albumOffset", offset)
+                .arg("songCount", tallies[2].next_count(size))
+                .arg("songOffset", offset)
+                .build();
+            let res = self.get("search3", args).await?;
+            let page = serde_json::from_value::<SearchResult>(res)?;
+            let found = [page.artists.len(), page.albums.len(), page.songs.len()];
+            let before = [artists.len(), albums.len(), songs.len()];
+
+            artists.extend(
+                page.artists
+                    .into_iter()
+                    .filter(|a| seen.insert(("artist", a.id.clone()))),
+            );
+            albums.extend(
+                page.albums
+                    .into_iter()
+                    .filter(|a| seen.insert(("album", a.id.clone()))),
+            );
+            songs.extend(
+                page.songs
+                    .into_iter()
+                    .filter(|s| seen.insert(("song", s.id.clone()))),
+            );
+            artists.truncate(cap);
+            albums.truncate(cap);
+            songs.truncate(cap);
+
+            let kept = [artists.len(), albums.len(), songs.len()];
+            for (i, tally) in tallies.iter_mut().enumerate() {
+                if !tally.done {
+                    tally.record(found[i], size, usize::MAX);
+                    // A server that ignores the offset sends the same page
+                    // again, which adds nothing new.
+                    tally.done |= kept[i] >= cap || kept[i] == before[i];
+                }
+            }
+            offset += size;
+        }
+
+        Ok((artists, albums, songs))
+    }
+
+    /// Returns albums, artists and songs whose names start with the given
+    /// prefix, ignoring case and any leading articles the server ignores
+    /// (such as "The").
+    ///
+    /// The Subsonic API has no prefix search, so this is a client-side
+    /// refinement of [`search`]: the same page is requested for each kind of
+    /// result, and anything that doesn't match the prefix is removed. Pages
+    /// may therefore hold fewer results than requested. The first search
+    /// also fetches the [ignored articles], which are then kept.
+    ///
+    /// [ignored articles]: #method.ignored_articles
+    ///
+    /// [`search`]: #method.search
+    pub async fn search_prefix(&self, prefix: &str, page: SearchPage) -> Result<SearchResult> {
+        let articles = self.ignored_articles().await?;
+        let mut result = self.search(prefix, page, page, page).await?;
+        result.retain_prefix(prefix, &articles);
+        Ok(result)
+    }
+
+    /// Returns the articles that the server ignores when sorting artists.
+    ///
+    /// The server only lists them along with every artist, so they are
+    /// fetched on first use and kept for the life of the client.
+    pub async fn ignored_articles(&self) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct _Artists {
+            #[serde(default)]
+            ignored_articles: String,
+        }
+
+        if let Some(ref articles) = *self.ignored_articles.lock().unwrap() {
+            return Ok(articles.clone());
+        }
+        let res = self.get("getArtists", Query::none()).await?;
+        let raw = serde_json::from_value::<_Artists>(res)?;
+        let articles = raw
+            .ignored_articles
+            .split_whitespace()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        *self.ignored_articles.lock().unwrap() = Some(articles.clone());
+        Ok(articles)
+    }
+
+    /// Stars every song, album, and artist in the selection in one request.
+    ///
+    /// # Errors
+    ///
+    /// Aside from the other errors the `Client` may cause, the method will
+    /// error if the selection is empty.
+    pub async fn star(&self, selection: &StarRequest) -> Result<()> {
+        if selection.is_empty() {
+            return Err(Error::Other("nothing selected to star"));
+        }
+        self.get("star", selection.to_query()).await?;
+        selection.ids().for_each(|id| self.invalidate(id));
+        Ok(())
+    }
+
+    /// Unstars every song, album, and artist in the selection in one request.
+    ///
+    /// # Errors
+    ///
+    /// Aside from the other errors the `Client` may cause, the method will
+    /// error if the selection is empty.
+    pub async fn unstar(&self, selection: &StarRequest) -> Result<()> {
+        if selection.is_empty() {
+            return Err(Error::Other("nothing selected to unstar"));
+        }
+        self.get("unstar", selection.to_query()).await?;
+        selection.ids().for_each(|id| self.invalidate(id));
+        Ok(())
+    }
+
+    /// Stars, rates, and scrobbles a song as one action, sending only the
+    /// requests needed for the annotations that are set.
+    ///
+    /// Nothing is sent if no annotations are set. The annotations are
+    /// applied in the order star, rating, scrobble, stopping at the first
+    /// that fails; those before it have already been applied, and any cached
+    /// copy of the entity is dropped either way.
+    ///
+    /// # Errors
+    ///
+    /// Aside from the other errors the `Client` may cause, the method will
+    /// error before sending anything if the rating is above 5.
+    pub async fn annotate(&self, id: &str, options: AnnotateOptions) -> Result<()> {
+        if options.rating.is_some_and(|r| r > 5) {
+            return Err(Error::Other("rating must be between 0 and 5 inclusive"));
+        }
+        if options.is_empty() {
+            return Ok(());
+        }
+
+        let applied = async {
+            if let Some(star) = options.star {
+                let endpoint = if star { "star" } else { "unstar" };
+                self.get(endpoint, Query::with("id", id)).await?;
+            }
+            if let Some(rating) = options.rating {
+                let args = Query::with("id", id).arg("rating", rating).build();
+                self.get("setRating", args).await?;
+            }
+            if let Some(submission) = options.scrobble {
+                let args = Query::with("id", id).arg("submission", submission).build();
+                self.get("scrobble", args).await?;
+            }
+            Ok(())
+        }
+        .await;
+        // Steps before a failed one have changed the entity all the same.
+        self.invalidate(id);
+        applied
+    }
+
+    /// Returns a list of all starred artists, albums, and songs.
+    pub async fn starred<U>(&self, folder_id: U) -> Result<SearchResult>
+    where
+        U: Into<Option<usize>>,
+    {
+        let res = self
+            .get("getStarred", Query::with("musicFolderId", folder_id.into()))
+            .await?;
+        Ok(serde_json::from_value::<SearchResult>(res)?)
+    }
+
+    /// Unstars every starred song, album, and artist, returning how many
+    /// were unstarred.
+    ///
+    /// Stars are removed in batches, so a failure part way through leaves
+    /// the later batches starred.
+    pub async fn clear_all_stars(&self) -> Result<usize> {
+        let res = self.get("getStarred2", Query::none()).await?;
+        let starred = serde_json::from_value::<SearchResult>(res)?;
+
+        let mut cleared = 0;
+        for batch in StarRequest::batches(&starred, UNSTAR_BATCH_SIZE) {
+            self.unstar(&batch).await?;
+            cleared += batch.ids().count();
+        }
+        Ok(cleared)
+    }
+
+    /// Clears the rating of every rated album, returning how many albums
+    /// were reset.
+    ///
+    /// The API only lists rated albums, by way of
+    /// [`ListType::Highest`](./enum.ListType.html#variant.Highest), so
+    /// ratings of songs and artists are left alone. Each album takes a
+    /// request of its own.
+    pub async fn reset_all_ratings(&self) -> Result<usize> {
+        // Collect every album first, as resetting them shifts the pages.
+        let mut rated = Vec::new();
+        loop {
+            let page = SearchPage {
+                count: RATED_PAGE_SIZE,
+                offset: rated.len(),
+            };
+            let albums = Album::list_in_folders(self, ListType::Highest, page, &[]).await?;
+            let len = albums.len();
+            rated.extend(albums);
+            if len < RATED_PAGE_SIZE {
+                break;
+            }
+        }
+
+        for album in &rated {
+            album.set_rating(self, 0).await?;
+        }
+        Ok(rated.len())
+    }
+}
+
+/// Returns the MIME type of a response, if it has one.
+fn content_type(res: &reqwest::Response) -> Option<&str> {
+    res.headers().get(CONTENT_TYPE)?.to_str().ok()
+}
+
+/// Returns whether a MIME type is HTML, as sent by proxies in place of the
+/// server's response.
+fn is_html(content_type: Option<&str>) -> bool {
+    content_type.is_some_and(|t| t.trim_start().starts_with("text/html"))
+}
+
+/// Returns whether a body that should be a Subsonic response looks like
+/// HTML instead, for proxies that don't label their pages.
+fn looks_like_html(body: &str, xml: bool) -> bool {
+    let start = body.trim_start();
+    if !xml {
+        return start.starts_with('<');
+    }
+    let start = start.get(..14).unwrap_or(start).to_ascii_lowercase();
+    start.starts_with("<!doctype html") || start.starts_with("<html")
+}
+
+/// Normalises a genre name for looking it up in the genre map.
+fn genre_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// Returns whether a MIME type is one the server sends Subsonic responses as.
+fn is_api_type(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    matches!(
+        mime,
+        "application/json" | "text/json" | "application/xml" | "text/xml"
+    )
+}
+
+/// Parses the body of a binary endpoint as a Subsonic response, if its MIME
+/// type says it is one.
+fn api_response(content_type: &str, body: &[u8]) -> Option<Response> {
+    let body = std::str::from_utf8(body).ok()?;
+    let mime = content_type.split(';').next()?.trim();
+    match mime {
+        "application/json" | "text/json" => Response::parse(body).ok(),
+        "application/xml" | "text/xml" => Response::parse_xml(body).ok(),
+        _ => None,
+    }
+}
+
+/// Keeps the episodes published after `since`, or all of them if nothing was
+/// published before.
+fn published_after(episodes: Vec<Episode>, since: Option<&str>) -> Vec<Episode> {
+    match since {
+        Some(since) => episodes
+            .into_iter()
+            .filter(|e| e.publish_date.as_str() > since)
+            .collect(),
+        None => episodes,
+    }
+}
+
+/// Resolves the location of a redirect relative to the URL that was redirected,
+/// keeping the original query string if the new location doesn't set one.
+///
+/// The authentication parameters are only carried over when the redirect
+/// stays on the same server, so that credentials are never sent to another
+/// one. They replace any the new location sets itself.
+fn redirect_target(from: &Url, location: &str) -> Result<Url> {
+    let mut next = from
+        .join(location)
+        .map_err(<url::ParseError as Into<UrlError>>::into)?;
+    let trusted = is_trusted_redirect(from, &next);
+    let is_auth = |k: &str| AUTH_KEYS.contains(&k);
+
+    let mut pairs = match next.query() {
+        Some(_) => next
+            .query_pairs()
+            .filter(|(k, _)| !is_auth(k))
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect::<Vec<_>>(),
+        None => from
+            .query_pairs()
+            .filter(|(k, _)| !is_auth(k))
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect(),
+    };
+    if trusted {
+        let auth = from.query_pairs().filter(|(k, _)| is_auth(k));
+        pairs.extend(auth.map(|(k, v)| (k.into_owned(), v.into_owned())));
     }
 
-    /// Returns a list of all starred artists, albums, and songs.
-    pub async fn starred<U>(&self, folder_id: U) -> Result<SearchResult>
-    where
-        U: Into<Option<usize>>,
-    {
-        let res = self
-            .get("getStarred", Query::with("musicFolderId", folder_id.into()))
-            .await?;
-        Ok(serde_json::from_value::<SearchResult>(res)?)
+    if pairs.is_empty() {
+        next.set_query(None);
+    } else {
+        next.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    Ok(next)
+}
+
+/// Returns whether a redirect stays on the same server: either the same scheme,
+/// host, and port, or an upgrade from `http` to `https` on the same host that
+/// keeps the port or moves from one default port to the other.
+fn is_trusted_redirect(from: &Url, next: &Url) -> bool {
+    if next.origin() == from.origin() {
+        return true;
+    }
+    let upgrade = from.scheme() == "http" && next.scheme() == "https";
+    let same_port = from.port() == next.port();
+    upgrade && from.host() == next.host() && same_port
+}
+
+/// Guesses the MIME type of an image from its first bytes.
+fn sniff_image_type(bytes: &[u8]) -> &'static str {
+    match bytes {
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [b'G', b'I', b'F', b'8', ..] => "image/gif",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Encodes the bytes as a base64 `data:` URI.
+fn data_uri(mime: &str, bytes: &[u8]) -> String {
+    let data = base64::engine::general_purpose::STANDARD.encode(bytes);
+    format!("data:{mime};base64,{data}")
+}
+
+/// Maps the result of a request to whether the credentials were accepted.
+fn accepted(res: Result<()>) -> Result<bool> {
+    match res {
+        Ok(()) => Ok(true),
+        Err(Error::Api(ApiError::WrongAuth, _)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Maps the result of a fetch to `None` if the server couldn't find the item.
+fn found<T>(res: Result<T>) -> Result<Option<T>> {
+    match res {
+        Ok(item) => Ok(Some(item)),
+        Err(Error::Api(ApiError::NotFound, _)) => Ok(None),
+        Err(e) => Err(e),
     }
 }
 
+/// Formats a URL for logging, hiding the authentication token, salt, and
+/// password.
+fn redacted(url: &Url) -> String {
+    let query = match url.query() {
+        Some(q) => q,
+        None => return url.to_string(),
+    };
+    let query = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((k, _)) if query::SECRET_KEYS.contains(&k) => format!("{k}=<redacted>"),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let mut base = url.clone();
+    base.set_query(None);
+    format!("{base}?{query}")
+}
+
+/// The account an API key belongs to.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TokenInfo {
+    /// The name of the user the key authenticates as.
+    pub username: String,
+}
+
 /// A representation of a license associated with a server.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct License {
     /// Whether the license is valid or not.
     pub valid: bool,
-    /// The email associated with the email.
-    pub email: String,
+    /// The email associated with the license. Servers that don't require a
+    /// license typically will not have this field.
+    #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
     /// An ISO8601 timestamp of the server's trial expiry.
+    #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub trial_expires: Option<String>,
     /// An ISO8601 timestamp of the server's license expiry. Servers still in
     /// the trial phase typically will not have this field.
+    #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub license_expires: Option<String>,
+    /// Whether the license enables video conversion. Only sent by servers
+    /// that extend the license response; see [`allows_video_conversion`]
+    /// for a best guess when it's missing.
+    ///
+    /// [`allows_video_conversion`]: #method.allows_video_conversion
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video_conversion: Option<bool>,
+}
+
+impl License {
+    /// Returns whether the server will convert videos for streaming.
+    ///
+    /// Uses the license's own `video_conversion` field if the server sent
+    /// it. Otherwise, this is a best guess from what the client knows about
+    /// the server: Subsonic itself only converts videos with a valid license
+    /// and API version 1.8.0 or later, while other servers don't gate
+    /// features behind a license. Servers the client hasn't [negotiated]
+    /// with are assumed to be Subsonic.
+    ///
+    /// [negotiated]: ./struct.Client.html#method.negotiate
+    pub fn allows_video_conversion(&self, client: &Client) -> bool {
+        if let Some(allowed) = self.video_conversion {
+            return allowed;
+        }
+        match client.server_flavor().unwrap_or(ServerFlavor::Subsonic) {
+            ServerFlavor::Subsonic => {
+                let ver = client.server_ver.unwrap_or(client.ver);
+                self.valid && ver >= "1.8.0".into()
+            }
+            _ => true,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -398,10 +2259,569 @@ mod tests {
         assert!(token_addr != legacy_addr);
         assert_eq!(
             legacy_addr,
-            "http://demo.subsonic.org/rest/ping?u=guest3&p=guest&v=1.8.0&c=sunk&f=json&"
+            "http://demo.subsonic.org/rest/ping?u=guest3&p=guest&v=1.8.0&c=sunk&f=json"
+        );
+    }
+
+    #[test]
+    fn redact_credentials() {
+        let url =
+            Url::parse("http://example.com/rest/ping?u=joe&t=26719a&s=c19b2d&v=1.14.0").unwrap();
+        assert_eq!(
+            redacted(&url),
+            "http://example.com/rest/ping?u=joe&t=<redacted>&s=<redacted>&v=1.14.0"
+        );
+
+        let url = Url::parse("http://example.com/rest/ping?u=joe&p=sesame&sort=t").unwrap();
+        assert_eq!(
+            redacted(&url),
+            "http://example.com/rest/ping?u=joe&p=<redacted>&sort=t"
+        );
+
+        let url = Url::parse("http://example.com/rest/ping?apiKey=abc123&v=1.16.1").unwrap();
+        assert_eq!(
+            redacted(&url),
+            "http://example.com/rest/ping?apiKey=<redacted>&v=1.16.1"
+        );
+    }
+
+    #[test]
+    fn api_key_replaces_credentials() {
+        let mut auth = SubsonicAuth::new("joe", "sesame");
+        auth.api_key = Some("abc/123".into());
+        assert_eq!(
+            auth.to_url_with_salt("1.16.1".into(), "c19b2d"),
+            "apiKey=abc%2F123&v=1.16.1&c=sunk&f=json"
+        );
+    }
+
+    #[test]
+    fn wrong_auth_is_not_an_error() {
+        assert!(accepted(Ok(())).unwrap());
+        assert!(!accepted(Err(ApiError::WrongAuth.into())).unwrap());
+        assert!(accepted(Err(ApiError::NotFound.into())).is_err());
+    }
+
+    #[test]
+    fn podcast_episodes_published_after() {
+        let episode = |id: &str, date: &str| {
+            serde_json::from_value::<Episode>(serde_json::json!({
+                "id": id,
+                "parent": "11",
+                "isDir": false,
+                "title": "Episode",
+                "album": "Podcast",
+                "artist": "Host",
+                "year": 2024,
+                "coverArt": "pod-1",
+                "size": 1024,
+                "contentType": "audio/mpeg",
+                "suffix": "mp3",
+                "duration": 60,
+                "bitRate": 128,
+                "isVideo": false,
+                "created": "2024-03-01T10:00:00.000Z",
+                "artistId": "",
+                "type": "podcast",
+                "streamId": id,
+                "channelId": "1",
+                "description": "",
+                "status": "completed",
+                "publishDate": date
+            }))
+            .unwrap()
+        };
+        let episodes = || {
+            vec![
+                episode("34", "2024-03-02T08:00:00.000Z"),
+                episode("33", "2024-03-01T08:00:00.000Z"),
+            ]
+        };
+
+        let added = published_after(episodes(), Some("2024-03-01T08:00:00.000Z"));
+        assert_eq!(added.len(), 1);
+        assert_eq!(added[0].id, 34);
+        assert!(published_after(episodes(), Some("2024-03-02T08:00:00.000Z")).is_empty());
+        assert_eq!(published_after(episodes(), None).len(), 2);
+    }
+
+    #[test]
+    fn cover_art_data_uri_encoding() {
+        let png = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        assert_eq!(sniff_image_type(&png), "image/png");
+        assert_eq!(sniff_image_type(&[0xFF, 0xD8, 0xFF, 0xE0]), "image/jpeg");
+        assert_eq!(sniff_image_type(b"<html>"), "application/octet-stream");
+
+        assert_eq!(
+            data_uri("image/png", &png),
+            "data:image/png;base64,iVBORw0KGgo="
+        );
+    }
+
+    #[test]
+    fn fixture_cover_art_error() {
+        let cli = test_util::fixture_site().unwrap();
+        let uri = tokio_test::block_on(cli.cover_art_data_uri("404", None));
+        assert!(matches!(uri, Err(Error::Api(ApiError::NotFound, _))));
+    }
+
+    #[test]
+    fn salt_length_and_charset() {
+        let mut rng = thread_rng();
+        let salt = SaltCharset::Alphanumeric.generate(&mut rng, 36);
+        assert_eq!(salt.len(), 36);
+        assert!(salt.chars().all(|c| c.is_ascii_alphanumeric()));
+
+        let salt = SaltCharset::Hex.generate(&mut rng, 12);
+        assert_eq!(salt.len(), 12);
+        assert!(salt.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')));
+
+        let cli = test_util::demo_site().unwrap().with_salt_length(2);
+        assert_eq!(cli.auth.salt_size, MIN_SALT_SIZE);
+    }
+
+    #[test]
+    fn token_matches_spec() {
+        // The example given in the Subsonic API documentation.
+        let auth = SubsonicAuth::new("joe", "sesame");
+        assert_eq!(
+            auth.to_url_with_salt("1.14.0".into(), "c19b2d"),
+            "u=joe&t=26719a1196d2a940705a59634eb18eab&s=c19b2d&v=1.14.0&c=sunk&f=json"
+        );
+    }
+
+    #[test]
+    fn guess_transcode_formats() {
+        let mut cli = Client::new("http://example.com", "guest", "guest").unwrap();
+        assert_eq!(cli.transcode_formats(), [StreamFormat::Mp3]);
+
+        cli.flavor = Some(ServerFlavor::Gonic);
+        assert_eq!(
+            cli.transcode_formats(),
+            [StreamFormat::Mp3, StreamFormat::Opus]
+        );
+
+        cli.server_ver = Some("1.5.0".into());
+        assert!(cli.transcode_formats().is_empty());
+    }
+
+    #[test]
+    fn restore_from_credentials() {
+        let cli = Client::new("http://example.com:4533/", "joe", "sesame")
+            .unwrap()
+            .with_client_name("myapp");
+        let creds = cli.credentials();
+        assert_eq!(creds.password(), Some("sesame"));
+        assert!(!format!("{creds:?}").contains("sesame"));
+
+        let saved = serde_json::to_value(&creds).unwrap();
+        assert_eq!(
+            saved,
+            serde_json::json!({
+                "url": "http://example.com:4533/",
+                "user": "joe",
+                "clientName": "myapp",
+            })
+        );
+
+        let loaded = serde_json::from_value::<Credentials>(saved).unwrap();
+        assert!(Client::from_credentials(loaded.clone()).is_err());
+        let restored = Client::from_credentials(loaded.with_password("sesame")).unwrap();
+        let url = restored.build_url("ping", Query::none()).unwrap();
+        assert!(url.starts_with("http://example.com:4533/rest/ping?u=joe&"));
+        assert!(url.contains("&c=myapp&"));
+    }
+
+    #[test]
+    fn send_player_id() {
+        let cli = Client::new("http://example.com", "guest", "guest")
+            .unwrap()
+            .with_player_id("living-room");
+        let stream = cli.stream_url_for("27", &StreamParams::new()).unwrap();
+        assert!(stream.ends_with("&playerId=living-room"));
+        let scrobble = cli.build_url("scrobble", Query::with("id", "27")).unwrap();
+        assert!(scrobble.ends_with("&id=27&playerId=living-room"));
+        let ping = cli.build_url("ping", Query::none()).unwrap();
+        assert!(!ping.contains("playerId"));
+
+        let restored = Client::from_credentials(cli.credentials()).unwrap();
+        assert_eq!(restored.player_id.as_deref(), Some("living-room"));
+    }
+
+    #[test]
+    fn default_page_size() {
+        let cli = Client::new("http://example.com", "guest", "guest").unwrap();
+        assert_eq!(cli.page_size(None), None);
+        assert_eq!(cli.page_size(0), None);
+        assert_eq!(cli.page_size(5), Some(5));
+
+        let cli = cli.with_default_page_size(100);
+        assert_eq!(cli.page_size(None), Some(100));
+        assert_eq!(cli.page_size(0), Some(100));
+        assert_eq!(cli.page_size(5), Some(5));
+    }
+
+    #[test]
+    fn encode_legacy_passwords() {
+        let auth = SubsonicAuth::new("joe", "sesame");
+        assert_eq!(
+            auth.to_url_with_salt("1.12.0".into(), "c19b2d"),
+            "u=joe&p=sesame&v=1.12.0&c=sunk&f=json"
+        );
+
+        let auth = SubsonicAuth::new("joe", "a&b=c");
+        assert_eq!(
+            auth.to_url_with_salt("1.12.0".into(), "c19b2d"),
+            "u=joe&p=enc:6126623d63&v=1.12.0&c=sunk&f=json"
+        );
+    }
+
+    #[test]
+    fn encode_client_name() {
+        let mut auth = SubsonicAuth::new("joe", "sesame");
+        auth.client_name = "my app/1.2.3".into();
+        assert!(auth
+            .to_url("1.12.0".into())
+            .ends_with("&c=my+app%2F1.2.3&f=json"));
+    }
+
+    #[test]
+    fn keep_shared_http_client() {
+        let http = ReqwestClient::builder()
+            .redirect(redirect::Policy::none())
+            .build()
+            .unwrap();
+        let cli = Client::new("http://example.com", "guest", "guest")
+            .unwrap()
+            .with_http_client(http)
+            .with_pool_idle_timeout(Duration::from_secs(30))
+            .unwrap();
+
+        assert!(cli.http.shared.is_some());
+        assert_eq!(cli.http.pool_idle_timeout, Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn scope_to_folder() {
+        let cli = Client::new("http://example.com", "guest", "guest").unwrap();
+        let scope = cli.scoped_to_folder(3);
+        assert_eq!(scope.folder(), 3);
+        assert!(std::ptr::eq(scope.client(), &cli));
+    }
+
+    #[test]
+    fn client_is_shareable() {
+        fn assert_shareable<T: Send + Sync>() {}
+        assert_shareable::<Client>();
+    }
+
+    #[test]
+    fn prefer_xml_format() {
+        let cli = test_util::demo_site().unwrap().with_prefer_xml(true);
+        assert!(cli
+            .build_url("ping", Query::none())
+            .unwrap()
+            .ends_with("&f=xml"));
+    }
+
+    #[test]
+    fn negotiate_lowers_target() {
+        let mut cli = test_util::demo_site().unwrap();
+        cli.set_server_version("1.12.0".into());
+        assert_eq!(cli.server_ver, Some("1.12.0".into()));
+        assert_eq!(cli.target_ver, "1.12.0".into());
+
+        cli.set_server_version("1.16.1".into());
+        assert_eq!(cli.target_ver, cli.ver);
+    }
+
+    #[test]
+    fn explicit_target_wins() {
+        let mut cli = test_util::demo_site().unwrap().with_target("1.8.0".into());
+        cli.set_server_version("1.16.1".into());
+        assert_eq!(cli.server_ver, Some("1.16.1".into()));
+        assert_eq!(cli.target_ver, "1.8.0".into());
+    }
+
+    #[test]
+    fn redirect_keeps_auth() {
+        let from = Url::parse("http://example.com/rest/ping?u=guest&p=guest&f=json").unwrap();
+
+        let moved = redirect_target(&from, "/music/rest/ping").unwrap();
+        assert_eq!(
+            moved.as_str(),
+            "http://example.com/music/rest/ping?f=json&u=guest&p=guest"
+        );
+
+        let merged = redirect_target(&from, "/music/rest/ping?u=other&id=1").unwrap();
+        assert_eq!(
+            merged.as_str(),
+            "http://example.com/music/rest/ping?id=1&u=guest&p=guest"
+        );
+
+        let https = redirect_target(&from, "https://example.com/rest/ping?t=x").unwrap();
+        assert_eq!(
+            https.as_str(),
+            "https://example.com/rest/ping?u=guest&p=guest"
+        );
+
+        let from = Url::parse("http://example.com:4533/rest/ping?u=guest&p=guest").unwrap();
+        let https = redirect_target(&from, "https://example.com:4533/rest/ping").unwrap();
+        assert_eq!(
+            https.as_str(),
+            "https://example.com:4533/rest/ping?u=guest&p=guest"
+        );
+    }
+
+    #[test]
+    fn redirect_drops_auth_across_origins() {
+        let from = Url::parse("http://example.com/rest/ping?u=guest&p=guest&f=json").unwrap();
+
+        let other = redirect_target(&from, "http://cdn.example.net/rest/ping").unwrap();
+        assert_eq!(other.as_str(), "http://cdn.example.net/rest/ping?f=json");
+
+        let https = Url::parse("https://example.com/rest/ping?u=guest&p=guest").unwrap();
+        let downgrade = redirect_target(&https, "http://example.com/rest/ping").unwrap();
+        assert_eq!(downgrade.as_str(), "http://example.com/rest/ping");
+
+        let port = redirect_target(&from, "http://example.com:8080/rest/ping").unwrap();
+        assert!(!port.as_str().contains("guest"));
+    }
+
+    #[test]
+    fn binary_error_responses() {
+        let fail = br#"{"subsonic-response": {
+            "status": "failed",
+            "version": "1.16.1",
+            "error": { "code": 70, "message": "Cover art not found" }
+        }}"#;
+        let response = api_response("application/json; charset=utf-8", fail).unwrap();
+        assert!(response.is_err());
+
+        assert!(api_response("image/png", fail).is_none());
+        assert!(api_response("application/json", &[0xff, 0xd8, 0xff]).is_none());
+    }
+
+    #[test]
+    fn detect_html_pages() {
+        assert!(is_html(Some("text/html; charset=utf-8")));
+        assert!(!is_html(Some("application/json")));
+        assert!(!is_html(None));
+
+        let login = "\n<!DOCTYPE html><html><body>Sign in</body></html>";
+        assert!(looks_like_html(login, false));
+        assert!(looks_like_html(login, true));
+        assert!(looks_like_html("<HTML>", true));
+        assert!(!looks_like_html(r#"{"subsonic-response": {}}"#, false));
+        assert!(!looks_like_html(
+            r#"<?xml version="1.0"?><subsonic-response/>"#,
+            true
+        ));
+
+        let err = Error::Html(StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            err.to_string(),
+            "received HTML instead of Subsonic response, status 401 Unauthorized"
+        );
+    }
+
+    #[test]
+    fn keep_port_in_url() {
+        let cli = Client::new("http://example.com:4533/", "guest", "guest").unwrap();
+        let url = cli.build_url("ping", Query::none()).unwrap();
+        assert!(url.starts_with("http://example.com:4533/rest/ping?"));
+        assert!(url.ends_with("&f=json"));
+    }
+
+    #[test]
+    fn fixture_negotiate() {
+        let mut cli = test_util::fixture_site().unwrap();
+        tokio_test::block_on(cli.negotiate()).unwrap();
+
+        assert_eq!(cli.server_ver, Some("1.16.1".into()));
+        assert_eq!(cli.server_flavor(), Some(ServerFlavor::Navidrome));
+    }
+
+    #[test]
+    fn override_version_per_request() {
+        let cli = test_util::fixture_site().unwrap();
+        let url = cli
+            .build_url_at("ping", Query::none(), "1.2.0".into())
+            .unwrap();
+        assert!(url.contains("&v=1.2.0&"));
+        assert!(cli
+            .build_url("ping", Query::none())
+            .unwrap()
+            .contains("&v=1.14.0&"));
+
+        let res = tokio_test::block_on(cli.get_at_version("1.2.0".into(), "ping", Query::none()));
+        assert_eq!(res.unwrap(), serde_json::Value::Null);
+    }
+
+    #[test]
+    fn fixture_refresh() {
+        let mut cli = test_util::fixture_site().unwrap();
+        assert!(cli.last_capabilities().is_none());
+
+        tokio_test::block_on(cli.refresh()).unwrap();
+        let caps = cli.last_capabilities().unwrap();
+        assert_eq!(caps.version(), "1.16.1".into());
+        assert!(caps.supports_api_key_auth());
+        assert_eq!(cli.server_flavor(), Some(ServerFlavor::Navidrome));
+    }
+
+    #[test]
+    fn fixture_search_all() {
+        let cli = test_util::fixture_site().unwrap();
+        let (artists, albums, songs) = tokio_test::block_on(cli.search_all("bellevue", 2)).unwrap();
+
+        assert_eq!(artists.len(), 1);
+        assert_eq!(albums.len(), 1);
+        assert_eq!(
+            songs.iter().map(|s| s.id.as_str()).collect::<Vec<_>>(),
+            ["27", "28"]
         );
     }
 
+    #[test]
+    fn fixture_annotate() {
+        let cli = test_util::fixture_site().unwrap();
+        let love = AnnotateOptions {
+            star: Some(true),
+            rating: Some(5),
+            scrobble: Some(true),
+        };
+        tokio_test::block_on(cli.annotate("27", love)).unwrap();
+        assert_eq!(cli.metrics().requests, 3);
+
+        let star = AnnotateOptions {
+            star: Some(false),
+            ..Default::default()
+        };
+        tokio_test::block_on(cli.annotate("27", star)).unwrap();
+        tokio_test::block_on(cli.annotate("27", AnnotateOptions::default())).unwrap();
+        assert_eq!(cli.metrics().requests, 4);
+
+        let bad = AnnotateOptions {
+            rating: Some(6),
+            ..love
+        };
+        assert!(tokio_test::block_on(cli.annotate("27", bad)).is_err());
+        assert_eq!(cli.metrics().requests, 4);
+    }
+
+    #[test]
+    fn fixture_annotate_invalidates_on_error() {
+        let cli = test_util::fixture_site()
+            .unwrap()
+            .with_entity_cache_ttl(Duration::from_secs(60));
+        tokio_test::block_on(Song::get(&cli, "28")).unwrap();
+
+        // Starring succeeds, but there is no fixture for the rating.
+        let love = AnnotateOptions {
+            star: Some(true),
+            rating: Some(5),
+            ..Default::default()
+        };
+        assert!(tokio_test::block_on(cli.annotate("28", love)).is_err());
+        assert_eq!(cli.metrics().requests, 3);
+
+        tokio_test::block_on(Song::get(&cli, "28")).unwrap();
+        assert_eq!(cli.metrics().requests, 4);
+    }
+
+    #[test]
+    fn fixture_ignored_articles_are_kept() {
+        let cli = test_util::fixture_site().unwrap();
+        let articles = tokio_test::block_on(cli.ignored_articles()).unwrap();
+        assert_eq!(articles.len(), 7);
+        assert_eq!(articles[0], "The");
+
+        tokio_test::block_on(cli.ignored_articles()).unwrap();
+        assert_eq!(cli.metrics().requests, 1);
+    }
+
+    #[test]
+    fn fixture_genre_map() {
+        let cli = test_util::fixture_site().unwrap();
+        let names = tokio_test::block_on(cli.genre_map()).unwrap();
+        assert_eq!(names.len(), 2);
+        assert_eq!(names["rock"], "Rock");
+        assert_eq!(names["electronic"], "Electronic");
+
+        tokio_test::block_on(cli.genre_map()).unwrap();
+        assert_eq!(cli.metrics().requests, 1);
+
+        let resolve = |g| tokio_test::block_on(cli.resolve_genre(g));
+        assert_eq!(resolve(" ROCK"), "Rock");
+        assert_eq!(resolve("rock"), "rock");
+        assert_eq!(resolve("Jazz"), "Jazz");
+        assert_eq!(cli.metrics().requests, 1);
+
+        tokio_test::block_on(cli.refresh_genre_map()).unwrap();
+        assert_eq!(cli.metrics().requests, 2);
+    }
+
+    #[test]
+    fn resolve_genre_without_genres() {
+        // Nothing listens here, so the genres can't be fetched.
+        let cli = Client::new("http://127.0.0.1:1", "guest", "guest").unwrap();
+        assert_eq!(tokio_test::block_on(cli.resolve_genre("rock")), "rock");
+    }
+
+    #[test]
+    fn fixture_api_key_fallback() {
+        let mut cli = test_util::legacy_fixture_site()
+            .unwrap()
+            .with_api_key("secret");
+        let caps = tokio_test::block_on(cli.capabilities()).unwrap();
+
+        assert!(!caps.supports_api_key_auth());
+        assert!(cli.auth.api_key.is_none());
+        assert_eq!(cli.server_ver, Some("1.16.1".into()));
+    }
+
+    #[test]
+    fn fixture_get_songs() {
+        let cli = test_util::fixture_site().unwrap();
+        let songs = tokio_test::block_on(cli.get_songs(&["28", "404", "28"]));
+
+        assert_eq!(songs.len(), 3);
+        assert_eq!(songs[0].as_ref().unwrap().id, "28");
+        assert!(songs[1].is_err());
+        assert_eq!(songs[2].as_ref().unwrap().id, "28");
+    }
+
+    #[test]
+    fn fixture_get_song_full() {
+        let cli = test_util::fixture_site().unwrap();
+        let (song, album, artist) = tokio_test::block_on(cli.get_song_full("28")).unwrap();
+
+        assert_eq!(song.id, "28");
+        assert_eq!(album.unwrap().id, "1");
+        // The artist's fixture is a "not found" error.
+        assert!(artist.is_none());
+        assert!(tokio_test::block_on(cli.get_song_full("404")).is_err());
+    }
+
+    #[test]
+    fn demo_standalone_stream_url() {
+        let cli = test_util::demo_site().unwrap();
+        let url = cli.stream_url_for("27", &StreamParams::new()).unwrap();
+
+        // Fetched without the client, as an external player would.
+        let status = tokio_test::block_on(async {
+            let res = reqwest::get(&url).await.unwrap();
+            assert!(!res
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|t| t.to_str().ok())
+                .is_some_and(|t| t.contains("json")));
+            res.status()
+        });
+        assert!(status.is_success());
+    }
+
     #[test]
     fn demo_ping() {
         let cli = test_util::demo_site().unwrap();
@@ -416,7 +2836,61 @@ mod tests {
         let license = tokio_test::block_on(async { cli.check_license().await.unwrap() });
 
         assert!(license.valid);
-        assert_eq!(license.email, String::from("demo@subsonic.org"));
+        assert_eq!(license.email.as_deref(), Some("demo@subsonic.org"));
+    }
+
+    #[test]
+    fn license_video_conversion() {
+        let license = |body: serde_json::Value| serde_json::from_value::<License>(body).unwrap();
+        let mut cli = Client::new("http://example.com", "guest", "guest").unwrap();
+        cli.server_ver = Some("1.16.1".into());
+
+        assert!(license(serde_json::json!({ "valid": true })).allows_video_conversion(&cli));
+        assert!(!license(serde_json::json!({ "valid": false })).allows_video_conversion(&cli));
+        let explicit = license(serde_json::json!({ "valid": true, "videoConversion": false }));
+        assert!(!explicit.allows_video_conversion(&cli));
+
+        cli.server_ver = Some("1.7.0".into());
+        assert!(!license(serde_json::json!({ "valid": true })).allows_video_conversion(&cli));
+
+        cli.flavor = Some(ServerFlavor::Navidrome);
+        assert!(license(serde_json::json!({ "valid": false })).allows_video_conversion(&cli));
+    }
+
+    #[test]
+    fn parse_token_info() {
+        let body = r#"{"subsonic-response": {
+            "status": "ok",
+            "version": "1.16.1",
+            "openSubsonic": true,
+            "tokenInfo": { "username": "joe" }
+        }}"#;
+        let res = Response::parse(body).unwrap().into_value().unwrap();
+        let info = serde_json::from_value::<TokenInfo>(res).unwrap();
+        assert_eq!(info.username, "joe");
+    }
+
+    #[test]
+    fn fixture_token_info_reuses_capabilities() {
+        let mut cli = test_util::fixture_site().unwrap().with_api_key("secret");
+        let info = tokio_test::block_on(cli.token_info()).unwrap();
+        assert_eq!(info.username, "guest");
+        let requests = cli.metrics().requests;
+
+        tokio_test::block_on(cli.token_info()).unwrap();
+        assert_eq!(cli.metrics().requests, requests + 1);
+    }
+
+    #[test]
+    fn parse_license_without_email() {
+        let license = serde_json::from_str::<License>(
+            r#"{ "valid" : true, "email" : "", "licenseExpires" : null }"#,
+        )
+        .unwrap();
+
+        assert!(license.valid);
+        assert_eq!(license.email, None);
+        assert_eq!(license.license_expires, None);
     }
 
     #[test]
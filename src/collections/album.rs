@@ -1,25 +1,47 @@
 //! Album APIs.
 
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
 use std::{fmt, result};
 
 use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
 use serde_json;
 
 use crate::query::{Arg, IntoArg, Query};
 use crate::search::SearchPage;
-use crate::{Client, Error, Media, Result, Song};
+use crate::{Client, Error, Media, Progress, Result, Song};
 
-#[allow(missing_docs)]
-#[derive(Debug, Clone, Copy)]
+/// The orderings that albums can be listed in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum ListType {
+    /// Alphabetically by artist.
+    #[default]
     AlphaByArtist,
+    /// Alphabetically by album name.
     AlphaByName,
+    /// The most frequently played albums first.
     Frequent,
+    /// The highest rated albums first.
     Highest,
-    Newest,
+    /// The albums most recently added to the server first.
+    RecentlyAdded,
+    /// In a random order.
     Random,
-    Recent,
+    /// The albums most recently played first.
+    RecentlyPlayed,
+    /// Starred albums only.
     Starred,
+    /// Albums released from `from` to `to`, inclusive. The list counts
+    /// down if `from` is the later year, though on Gonic only within each
+    /// page; its pages start from the oldest albums.
+    ByYear {
+        /// The first year to list.
+        from: u32,
+        /// The last year to list.
+        to: u32,
+    },
 }
 
 impl fmt::Display for ListType {
@@ -30,21 +52,16 @@ impl fmt::Display for ListType {
             AlphaByName => "alphabeticalByName",
             Frequent => "frequent",
             Highest => "highest",
-            Newest => "newest",
+            RecentlyAdded => "newest",
             Random => "random",
-            Recent => "recent",
+            RecentlyPlayed => "recent",
             Starred => "starred",
+            ByYear { .. } => "byYear",
         };
         write!(f, "{fmt}")
     }
 }
 
-impl Default for ListType {
-    fn default() -> Self {
-        ListType::AlphaByArtist
-    }
-}
-
 impl IntoArg for ListType {
     fn into_arg(self) -> Arg {
         self.to_string().into_arg()
@@ -52,18 +69,48 @@ impl IntoArg for ListType {
 }
 
 #[allow(missing_docs)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 #[readonly::make]
 pub struct Album {
     pub id: String,
     pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub artist: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub artist_id: Option<String>,
+    #[serde(rename = "coverArt")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub cover_id: Option<String>,
     pub duration: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    /// The year the album was released. Compilations and untagged albums
+    /// often have none; servers that send `0` for these are read as `None`.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub year: Option<u64>,
+    /// The album's genre, if it has a single one.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub genre: Option<String>,
     pub song_count: u64,
+    /// How many times songs from the album have been played.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub play_count: Option<u64>,
+    /// An ISO8601 timestamp of when the album was last played. Only sent by
+    /// OpenSubsonic servers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub played: Option<String>,
+    /// An ISO8601 timestamp of when the user starred the album, or `None` if
+    /// it isn't starred.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub starred: Option<String>,
+    /// The [MusicBrainz](https://musicbrainz.org/) ID of the release, if it is
+    /// tagged with one.
+    #[serde(rename = "musicBrainzId")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub musicbrainz_id: Option<String>,
+    #[serde(rename = "song")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub songs: Vec<Song>,
 }
 
@@ -85,7 +132,135 @@ impl Album {
         page: SearchPage,
         folder: usize,
     ) -> Result<Vec<Album>> {
-        self::get_albums(client, list_type, page.count, page.offset, folder).await
+        self::get_albums(client, list_type, page.count, page.offset, &[folder]).await
+    }
+
+    /// Lists the albums in any of the given music folders. Supports paging.
+    ///
+    /// The folder IDs are sent as repeated `musicFolderId` parameters, which
+    /// some OpenSubsonic servers accept. Other servers only honour the
+    /// first folder.
+    pub async fn list_in_folders(
+        client: &Client,
+        list_type: ListType,
+        page: SearchPage,
+        folders: &[usize],
+    ) -> Result<Vec<Album>> {
+        self::get_albums(client, list_type, page.count, page.offset, folders).await
+    }
+
+    /// Lists the albums in any of the given genres. Supports paging.
+    ///
+    /// The genres are sent as repeated `genre` parameters, which some
+    /// OpenSubsonic servers accept, while others only honour one of them.
+    /// The client learns which kind the server is from the pages it gets
+    /// back, and until then also requests the genres missing from a page on
+    /// their own. On servers that only honour one genre, each genre is paged
+    /// on its own, so a merged page can hold up to `page.count` albums per
+    /// genre. Albums in several genres are listed once.
+    ///
+    /// Genres are matched ignoring case, using the client's [genre map].
+    ///
+    /// [genre map]: ../struct.Client.html#method.genre_map
+    ///
+    /// # Errors
+    ///
+    /// Aside from errors the `Client` may cause, the method will error if no
+    /// genres are given.
+    pub async fn list_by_genres<U>(
+        client: &Client,
+        genres: &[&str],
+        page: SearchPage,
+        folder: U,
+    ) -> Result<Vec<Album>>
+    where
+        U: Into<Option<usize>>,
+    {
+        if genres.is_empty() {
+            return Err(Error::Other("no genres given"));
+        }
+        let folder = folder.into();
+        let mut resolved = Vec::with_capacity(genres.len());
+        for genre in genres {
+            resolved.push(client.resolve_genre(genre).await);
+        }
+        let resolved = resolved.iter().map(String::as_str).collect::<Vec<_>>();
+        let genres = &resolved[..];
+
+        let mut albums = Vec::new();
+        let mut seen = HashSet::new();
+        let multi = client.multi_genre();
+        if multi == Some(false) {
+            for genre in genres {
+                let more = get_albums_by_genre(client, &[genre], page, folder).await?;
+                merge_albums(&mut albums, &mut seen, more);
+            }
+            return Ok(albums);
+        }
+
+        let first = get_albums_by_genre(client, genres, page, folder).await?;
+        // Servers list 10 albums unless told otherwise.
+        let full = first.len() >= client.page_size(page.count).unwrap_or(10);
+        let found = genres_found(&first, genres);
+        merge_albums(&mut albums, &mut seen, first);
+        if genres.len() == 1 || multi == Some(true) {
+            return Ok(albums);
+        }
+        if found.len() > 1 {
+            client.set_multi_genre(true);
+            return Ok(albums);
+        }
+
+        for genre in genres.iter().filter(|g| !found.contains(g)) {
+            let more = get_albums_by_genre(client, &[genre], page, folder).await?;
+            if !full && more.iter().any(|a| !seen.contains(&a.id)) {
+                client.set_multi_genre(false);
+            }
+            merge_albums(&mut albums, &mut seen, more);
+        }
+        Ok(albums)
+    }
+
+    /// Returns all albums added to the server after `since`, newest first.
+    ///
+    /// `since` is an ISO8601 timestamp, such as `"2018-01-01T00:00:00.000Z"`.
+    /// The method pages through the [`RecentlyAdded`] list and stops as soon
+    /// as it reaches an album that is not newer than `since`, so a sync only
+    /// costs as many requests as there are new albums.
+    ///
+    /// This relies on the server sorting the list by the date albums
+    /// were added, and on it reporting a `created` timestamp in the same
+    /// format as `since`; the timestamps are compared as strings. An album
+    /// without a `created` timestamp also ends the scan.
+    ///
+    /// [`RecentlyAdded`]: ./enum.ListType.html#variant.RecentlyAdded
+    pub async fn added_since<U>(client: &Client, since: &str, folder: U) -> Result<Vec<Album>>
+    where
+        U: Into<Option<usize>>,
+    {
+        const PAGE_SIZE: usize = 50;
+
+        let folder = folder.into();
+        let mut added = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = self::get_albums(
+                client,
+                ListType::RecentlyAdded,
+                Some(PAGE_SIZE),
+                Some(offset),
+                folder.as_ref().map_or(&[][..], std::slice::from_ref),
+            )
+            .await?;
+            let len = page.len();
+            let (newer, done) = split_newer(page, since);
+            added.extend(newer);
+
+            if done || len < PAGE_SIZE {
+                return Ok(added);
+            }
+            offset += len;
+        }
     }
 
     /// Returns all songs in the album.
@@ -97,6 +272,65 @@ impl Album {
         }
     }
 
+    /// Downloads each of the album's songs into `dir`, named with
+    /// [`Song::suggested_filename`]. The directory is created if it doesn't
+    /// exist.
+    ///
+    /// Songs are downloaded one at a time, in album order. Each song's result
+    /// holds the path it was written to, or why it failed; a song that fails
+    /// doesn't stop the rest, and any partly written file it left is removed.
+    /// Existing files with the same name are overwritten.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the album's songs can't be fetched, or the directory can't
+    /// be created.
+    ///
+    /// [`Song::suggested_filename`]: ../struct.Song.html#method.suggested_filename
+    pub async fn download_all(&self, client: &Client, dir: &Path) -> Result<Vec<Result<PathBuf>>> {
+        self.download_all_with(client, dir, |_| {}).await
+    }
+
+    /// Downloads each of the album's songs into `dir` like [`download_all`],
+    /// calling `on_progress` as each chunk is written and as each song
+    /// finishes.
+    ///
+    /// [`download_all`]: #method.download_all
+    pub async fn download_all_with<F>(
+        &self,
+        client: &Client,
+        dir: &Path,
+        mut on_progress: F,
+    ) -> Result<Vec<Result<PathBuf>>>
+    where
+        F: FnMut(&Progress),
+    {
+        let songs = self.songs(client).await?;
+        fs::create_dir_all(dir)?;
+
+        let mut paths = Vec::with_capacity(songs.len());
+        for (i, song) in songs.iter().enumerate() {
+            let path = dir.join(song.suggested_filename());
+            let mut progress = Progress {
+                bytes_done: 0,
+                total: None,
+                current_file: path.clone(),
+                files_done: i,
+                files_total: songs.len(),
+            };
+            let result = download_song(client, song, path, |done, total| {
+                progress.bytes_done = done;
+                progress.total = total;
+                on_progress(&progress);
+            })
+            .await;
+            progress.files_done += 1;
+            on_progress(&progress);
+            paths.push(result);
+        }
+        Ok(paths)
+    }
+
     /// Returns detailed information about the album.
     pub async fn info(&self, client: &Client) -> Result<AlbumInfo> {
         let res = client
@@ -134,14 +368,25 @@ impl<'de> Deserialize<'de> for Album {
         struct _Album {
             id: String,
             name: String,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
             artist: Option<String>,
             artist_id: Option<String>,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
             cover_art: Option<String>,
             song_count: u64,
             duration: u64,
-            // created: String,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+            created: Option<String>,
             year: Option<u64>,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
             genre: Option<String>,
+            play_count: Option<u64>,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+            played: Option<String>,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+            starred: Option<String>,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+            music_brainz_id: Option<String>,
             #[serde(default)]
             song: Vec<Song>,
         }
@@ -155,14 +400,68 @@ impl<'de> Deserialize<'de> for Album {
             artist_id: raw.artist_id,
             cover_id: raw.cover_art,
             duration: raw.duration,
-            year: raw.year,
+            created: raw.created,
+            year: raw.year.filter(|&y| y > 0),
             genre: raw.genre,
             song_count: raw.song_count,
+            play_count: raw.play_count,
+            played: raw.played,
+            starred: raw.starred,
+            musicbrainz_id: raw.music_brainz_id,
             songs: raw.song,
         })
     }
 }
 
+/// An album as returned by the folder-based `getAlbumList`.
+///
+/// Servers before API version 1.8.0 don't organise media by ID3 tags, and
+/// return albums as directory entries instead.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FolderAlbum {
+    id: String,
+    title: String,
+    #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+    album: Option<String>,
+    #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+    artist: Option<String>,
+    #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+    cover_art: Option<String>,
+    #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+    created: Option<String>,
+    year: Option<u64>,
+    #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+    genre: Option<String>,
+    play_count: Option<u64>,
+    #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+    played: Option<String>,
+    #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+    starred: Option<String>,
+}
+
+impl From<FolderAlbum> for Album {
+    fn from(raw: FolderAlbum) -> Album {
+        Album {
+            id: raw.id,
+            name: raw.album.unwrap_or(raw.title),
+            artist: raw.artist,
+            artist_id: None,
+            cover_id: raw.cover_art,
+            duration: 0,
+            created: raw.created,
+            year: raw.year.filter(|&y| y > 0),
+            genre: raw.genre,
+            song_count: 0,
+            play_count: raw.play_count,
+            played: raw.played,
+            starred: raw.starred,
+            musicbrainz_id: None,
+            songs: Vec::new(),
+        }
+    }
+}
+
 #[async_trait::async_trait]
 impl Media for Album {
     fn has_cover_art(&self) -> bool {
@@ -232,42 +531,184 @@ impl<'de> Deserialize<'de> for AlbumInfo {
     }
 }
 
+/// Takes albums from a date-sorted list while they are newer than `since`.
+/// Also returns whether an album that isn't newer was reached.
+fn split_newer(albums: Vec<Album>, since: &str) -> (Vec<Album>, bool) {
+    let mut newer = Vec::with_capacity(albums.len());
+    for album in albums {
+        match album.created {
+            Some(ref created) if created.as_str() > since => newer.push(album),
+            _ => return (newer, true),
+        }
+    }
+    (newer, false)
+}
+
+impl Serialize for AlbumInfo {
+    fn serialize<S>(&self, ser: S) -> result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut info = ser.serialize_struct("AlbumInfo", 6)?;
+        info.serialize_field("notes", &self.notes)?;
+        info.serialize_field("musicBrainzId", &self.musicbrainz_id)?;
+        info.serialize_field("lastFmUrl", &self.lastfm_url)?;
+        info.serialize_field("smallImageUrl", &self.image_urls.0)?;
+        info.serialize_field("mediumImageUrl", &self.image_urls.1)?;
+        info.serialize_field("largeImageUrl", &self.image_urls.2)?;
+        info.end()
+    }
+}
+
 async fn get_album(client: &Client, id: &str) -> Result<Album> {
-    let res = client.get("getAlbum", Query::with("id", id)).await?;
+    let res = client.get_entity("getAlbum", id).await?;
     Ok(serde_json::from_value::<Album>(res)?)
 }
 
+/// Requests an album list, from the folder-based endpoint if the server is
+/// too old for the ID3 one.
+async fn list_albums(client: &Client, args: Query) -> Result<Vec<Album>> {
+    if client.target_ver < "1.8.0".into() {
+        let album = client.get("getAlbumList", args).await?;
+        return Ok(get_list_as!(album, FolderAlbum)
+            .into_iter()
+            .map(Album::from)
+            .collect());
+    }
+
+    let album = client.get("getAlbumList2", args).await?;
+    Ok(get_list_as!(album, Album))
+}
+
+async fn get_albums_by_genre(
+    client: &Client,
+    genres: &[&str],
+    page: SearchPage,
+    folder: Option<usize>,
+) -> Result<Vec<Album>> {
+    let args = Query::with("type", "byGenre")
+        .arg_list("genre", genres)
+        .arg("size", client.page_size(page.count))
+        .arg("offset", page.offset)
+        .arg("musicFolderId", folder)
+        .build();
+    list_albums(client, args).await
+}
+
+/// Downloads the song to `path`, removing the file if the download fails.
+async fn download_song<F>(
+    client: &Client,
+    song: &Song,
+    path: PathBuf,
+    on_progress: F,
+) -> Result<PathBuf>
+where
+    F: FnMut(u64, Option<u64>),
+{
+    let mut file = File::create(&path)?;
+    match song.download_to(client, &mut file, on_progress).await {
+        Ok(_) => Ok(path),
+        Err(e) => {
+            drop(file);
+            let _ = fs::remove_file(&path);
+            Err(e)
+        }
+    }
+}
+
+/// Returns the requested genres that the albums belong to.
+fn genres_found<'a>(albums: &[Album], genres: &[&'a str]) -> Vec<&'a str> {
+    genres
+        .iter()
+        .filter(|g| {
+            albums.iter().any(|a| {
+                a.genre
+                    .as_deref()
+                    .is_some_and(|ag| ag.eq_ignore_ascii_case(g))
+            })
+        })
+        .copied()
+        .collect()
+}
+
+/// Appends the albums not already in the list, keeping the list's order.
+fn merge_albums(albums: &mut Vec<Album>, seen: &mut HashSet<String>, more: Vec<Album>) {
+    for album in more {
+        if seen.insert(album.id.clone()) {
+            albums.push(album);
+        }
+    }
+}
+
 async fn get_albums<U>(
     client: &Client,
     list_type: ListType,
     size: U,
     offset: U,
-    folder_id: U,
+    folders: &[usize],
 ) -> Result<Vec<Album>>
 where
     U: Into<Option<usize>>,
 {
+    let (from_year, to_year) = match list_type {
+        ListType::ByYear { from, to } => (Some(from), Some(to)),
+        _ => (None, None),
+    };
     let args = Query::new()
         .arg("type", list_type)
-        .arg("size", size.into())
+        .arg("fromYear", from_year)
+        .arg("toYear", to_year)
+        .arg("size", client.page_size(size))
         .arg("offset", offset.into())
-        .arg("musicFolderId", folder_id.into())
+        .arg_list("musicFolderId", folders)
         .build();
 
-    let album = client.get("getAlbumList2", args).await?;
-    Ok(get_list_as!(album, Album))
+    let mut albums = list_albums(client, args).await?;
+    if let Some(flavor) = client.server_flavor() {
+        flavor.fix_album_list(list_type, &mut albums);
+    }
+    Ok(albums)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::test_util;
+    use crate::ApiError;
+
+    #[test]
+    fn list_type_params() {
+        use super::ListType::*;
+        let params = [
+            (AlphaByArtist, "alphabeticalByArtist"),
+            (AlphaByName, "alphabeticalByName"),
+            (Frequent, "frequent"),
+            (Highest, "highest"),
+            (RecentlyAdded, "newest"),
+            (Random, "random"),
+            (RecentlyPlayed, "recent"),
+            (Starred, "starred"),
+            (
+                ByYear {
+                    from: 1999,
+                    to: 2001,
+                },
+                "byYear",
+            ),
+        ];
+        for (list, param) in &params {
+            assert_eq!(
+                Query::with("type", *list).to_string(),
+                format!("type={param}")
+            );
+        }
+    }
 
     #[test]
     fn demo_get_albums() {
         let srv = test_util::demo_site().unwrap();
         let albums = tokio_test::block_on(async {
-            get_albums(&srv, ListType::AlphaByArtist, None, None, None)
+            get_albums(&srv, ListType::AlphaByArtist, None, None, &[])
                 .await
                 .unwrap()
         });
@@ -275,6 +716,119 @@ mod tests {
         assert!(!albums.is_empty())
     }
 
+    #[test]
+    fn fixture_get_album() {
+        let srv = test_util::fixture_site().unwrap();
+        let album = tokio_test::block_on(Album::get(&srv, "1")).unwrap();
+
+        assert_eq!(album.name, "Bellevue");
+        assert_eq!(album.songs.len(), 2);
+        assert!(album.songs[0].replay_gain.is_some());
+        assert!(album.songs[1].replay_gain.is_none());
+    }
+
+    #[test]
+    fn fixture_download_all() {
+        let srv = test_util::fixture_site().unwrap();
+        let dir = std::env::temp_dir().join(format!("sunk-album-{}", std::process::id()));
+        let paths = tokio_test::block_on(async {
+            let album = Album::get(&srv, "1").await.unwrap();
+            album.download_all(&srv, &dir).await.unwrap()
+        });
+
+        assert_eq!(paths.len(), 2);
+        let first = paths[0].as_ref().unwrap();
+        assert!(first.ends_with("01 - Misteur Valaire - Bellevue Avenue.mp3"));
+        assert_eq!(fs::read(first).unwrap(), b"ID3 not really an mp3\n");
+        // The server reports an error for the second song, which is not
+        // saved as its file.
+        assert!(matches!(paths[1], Err(Error::Api(ApiError::NotFound, _))));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fixture_download_all_progress() {
+        let srv = test_util::fixture_site().unwrap();
+        let dir = std::env::temp_dir().join(format!("sunk-progress-{}", std::process::id()));
+        let mut events = Vec::new();
+        tokio_test::block_on(async {
+            let album = Album::get(&srv, "1").await.unwrap();
+            album
+                .download_all_with(&srv, &dir, |p| events.push(p.clone()))
+                .await
+                .unwrap()
+        });
+
+        let first = dir.join("01 - Misteur Valaire - Bellevue Avenue.mp3");
+        let summary = events
+            .iter()
+            .map(|p| (p.bytes_done, p.total, p.files_done, p.files_total))
+            .collect::<Vec<_>>();
+        // The server reports an error for the second song, so it finishes
+        // without any bytes.
+        assert_eq!(
+            summary,
+            [(22, Some(22), 0, 2), (22, Some(22), 1, 2), (0, None, 2, 2)]
+        );
+        assert_eq!(events[0].current_file, first);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fixture_list_by_genres_single_genre_server() {
+        let cli = test_util::fixture_site().unwrap();
+        let genres = ["ROCK", "electronic"];
+        let list = || {
+            let albums = tokio_test::block_on(Album::list_by_genres(
+                &cli,
+                &genres,
+                SearchPage::new(),
+                None,
+            ))
+            .unwrap();
+            albums.into_iter().map(|a| a.id).collect::<Vec<_>>()
+        };
+
+        // The server only honoured the first genre, which the request for
+        // the second shows.
+        assert_eq!(list(), ["1", "2"]);
+        assert_eq!(cli.multi_genre(), Some(false));
+        assert_eq!(cli.metrics().requests, 3);
+
+        assert_eq!(list(), ["1", "2"]);
+        assert_eq!(cli.metrics().requests, 5);
+    }
+
+    #[test]
+    fn merge_genre_lists() {
+        let album = |id: &str, genre: &str| {
+            serde_json::from_value::<Album>(serde_json::json!({
+                "id": id,
+                "name": "Bellevue",
+                "duration": 0,
+                "songCount": 0,
+                "genre": genre
+            }))
+            .unwrap()
+        };
+
+        let rock = vec![album("1", "Rock"), album("2", "Rock")];
+        assert_eq!(genres_found(&rock, &["rock", "Jazz"]), ["rock"]);
+        let mixed = vec![album("1", "Rock"), album("3", "Jazz")];
+        assert_eq!(genres_found(&mixed, &["Rock", "Jazz"]), ["Rock", "Jazz"]);
+
+        let mut seen = rock.iter().map(|a| a.id.clone()).collect();
+        let mut merged = rock;
+        merge_albums(
+            &mut merged,
+            &mut seen,
+            vec![album("2", "Jazz"), album("3", "Jazz")],
+        );
+        let ids = merged.iter().map(|a| a.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, ["1", "2", "3"]);
+    }
+
     #[test]
     fn parse_album() {
         let parsed = serde_json::from_value::<Album>(raw()).unwrap();
@@ -282,6 +836,68 @@ mod tests {
         assert_eq!(parsed.id, "1");
         assert_eq!(parsed.name, String::from("Bellevue"));
         assert_eq!(parsed.song_count, 9);
+        assert_eq!(parsed.play_count, Some(2223));
+    }
+
+    #[test]
+    fn parse_musicbrainz_ids() {
+        let mut value = raw();
+        value["musicBrainzId"] = "0e8ee5a3-7ba2-44bd-9de5-19c2b4cbda77".into();
+        value["song"][0]["musicBrainzId"] = "".into();
+        value["song"][1]["musicBrainzId"] = "6b1a6ca1-5d2a-4c5e-a1b1-fdc3cfc66f35".into();
+        let parsed = serde_json::from_value::<Album>(value).unwrap();
+
+        assert_eq!(
+            parsed.musicbrainz_id.as_deref(),
+            Some("0e8ee5a3-7ba2-44bd-9de5-19c2b4cbda77")
+        );
+        assert_eq!(parsed.songs[0].musicbrainz_id, None);
+        assert!(parsed.songs[1].musicbrainz_id.is_some());
+        assert_eq!(parsed.songs[2].musicbrainz_id, None);
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap()["musicBrainzId"],
+            "0e8ee5a3-7ba2-44bd-9de5-19c2b4cbda77"
+        );
+
+        let artist = serde_json::from_value::<crate::Artist>(serde_json::json!({
+            "id": "1",
+            "name": "Misteur Valaire",
+            "albumCount": 0,
+        }))
+        .unwrap();
+        assert_eq!(artist.musicbrainz_id, None);
+    }
+
+    #[test]
+    fn parse_missing_year_and_genre() {
+        let mut tagged = raw();
+        tagged["year"] = 2013.into();
+        tagged["genre"] = "Electronic".into();
+        let parsed = serde_json::from_value::<Album>(tagged).unwrap();
+        assert_eq!(parsed.year, Some(2013));
+        assert_eq!(parsed.genre.as_deref(), Some("Electronic"));
+
+        let compilation = serde_json::from_value::<Album>(serde_json::json!({
+            "id": "2",
+            "name": "Various Artists",
+            "duration": 0,
+            "songCount": 0,
+            "year": 0,
+            "genre": "",
+        }))
+        .unwrap();
+        assert_eq!(compilation.year, None);
+        assert_eq!(compilation.genre, None);
+
+        let untagged = serde_json::from_value::<Album>(serde_json::json!({
+            "id": "3",
+            "name": "Untagged",
+            "duration": 0,
+            "songCount": 0,
+        }))
+        .unwrap();
+        assert_eq!(untagged.year, None);
+        assert_eq!(untagged.genre, None);
     }
 
     #[test]
@@ -293,6 +909,59 @@ mod tests {
         assert_eq!(parsed.songs[0].duration, Some(198));
     }
 
+    #[test]
+    fn serialize_album_round_trip() {
+        let parsed = serde_json::from_value::<Album>(raw()).unwrap();
+        let value = serde_json::to_value(&parsed).unwrap();
+
+        assert_eq!(value["coverArt"], "al-1");
+        assert_eq!(value["song"].as_array().unwrap().len(), 9);
+
+        let reparsed = serde_json::from_value::<Album>(value).unwrap();
+        assert_eq!(reparsed.id, parsed.id);
+        assert_eq!(reparsed.songs.len(), parsed.songs.len());
+    }
+
+    #[test]
+    fn newer_albums_stop_at_since() {
+        let mut old = serde_json::from_value::<Album>(raw()).unwrap();
+        old.created = Some("2017-03-12T11:07:25.000Z".into());
+        let mut new = old.clone();
+        new.created = Some("2018-01-01T10:30:10.000Z".into());
+
+        let (newer, done) = split_newer(vec![new.clone(), old], "2017-06-01T00:00:00.000Z");
+        assert_eq!(newer.len(), 1);
+        assert!(done);
+
+        let (newer, done) = split_newer(vec![new], "2017-06-01T00:00:00.000Z");
+        assert_eq!(newer.len(), 1);
+        assert!(!done);
+    }
+
+    #[test]
+    fn parse_folder_album() {
+        let raw = serde_json::from_str::<FolderAlbum>(
+            r#"{
+            "id" : "25",
+            "parent" : "24",
+            "isDir" : true,
+            "title" : "Bellevue",
+            "artist" : "Misteur Valaire",
+            "year" : 2013,
+            "coverArt" : "25",
+            "created" : "2017-03-12T11:07:25.000Z"
+        }"#,
+        )
+        .unwrap();
+        let parsed = Album::from(raw);
+
+        assert_eq!(parsed.id, "25");
+        assert_eq!(parsed.name, String::from("Bellevue"));
+        assert_eq!(parsed.artist, Some(String::from("Misteur Valaire")));
+        assert_eq!(parsed.cover_id, Some(String::from("25")));
+        assert!(parsed.songs.is_empty());
+    }
+
     fn raw() -> serde_json::Value {
         serde_json::from_str(r#"{
          "id" : "1",
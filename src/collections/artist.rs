@@ -1,8 +1,10 @@
 //! Artist APIs.
 
+use std::collections::HashSet;
 use std::{fmt, result};
 
 use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
 use serde_json;
 
 use crate::query::Query;
@@ -10,13 +12,31 @@ use crate::{Album, Client, Error, Media, Result, Song};
 
 /// Basic information about an artist.
 #[allow(missing_docs)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Artist {
     pub id: String,
     pub name: String,
+    #[serde(rename = "coverArt")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     cover_id: Option<String>,
+    #[serde(rename = "album")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     albums: Vec<Album>,
     pub album_count: usize,
+    /// A URL for the artist's image, usually hosted outside the server. Only
+    /// sent by some servers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artist_image_url: Option<String>,
+    /// An ISO8601 timestamp of when the user starred the artist, or `None`
+    /// if it isn't starred.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub starred: Option<String>,
+    /// The [MusicBrainz](https://musicbrainz.org/) ID of the artist, if it is
+    /// tagged with one.
+    #[serde(rename = "musicBrainzId")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub musicbrainz_id: Option<String>,
 }
 
 /// Detailed information about an artist.
@@ -34,6 +54,49 @@ pub struct ArtistInfo {
     similar_artists: Vec<Artist>,
 }
 
+/// The sizes an artist's image is available in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageSize {
+    #[allow(missing_docs)]
+    Small,
+    #[allow(missing_docs)]
+    Medium,
+    #[allow(missing_docs)]
+    Large,
+}
+
+impl ArtistInfo {
+    /// Returns the URL of the artist's image in the given size, if the server
+    /// provided one.
+    pub fn image_url(&self, size: ImageSize) -> Option<&str> {
+        let url = match size {
+            ImageSize::Small => &self.image_urls.0,
+            ImageSize::Medium => &self.image_urls.1,
+            ImageSize::Large => &self.image_urls.2,
+        };
+        if url.is_empty() {
+            None
+        } else {
+            Some(url)
+        }
+    }
+
+    /// Downloads the artist's image in the given size.
+    ///
+    /// The images are hosted externally (typically by last.fm) rather than by
+    /// the Subsonic server, so the request is sent without any of the
+    /// client's authentication details.
+    ///
+    /// # Errors
+    ///
+    /// Aside from errors that the `Client` may cause, the method will error
+    /// if there is no image available in the given size.
+    pub async fn download_image(&self, client: &Client, size: ImageSize) -> Result<Vec<u8>> {
+        let url = self.image_url(size).ok_or(Error::Other("no image found"))?;
+        client.get_external(url).await
+    }
+}
+
 impl Artist {
     #[allow(missing_docs)]
     pub async fn get(client: &Client, id: &str) -> Result<Artist> {
@@ -74,7 +137,7 @@ impl Artist {
         U: Into<Option<usize>>,
     {
         let args = Query::with("id", self.id.as_ref())
-            .arg("count", count.into())
+            .arg("count", client.page_size(count))
             .arg("includeNotPresent", include_not_present.into())
             .build();
         let res = serde_json::from_value::<ArtistInfo>(client.get("getArtistInfo", args).await?)?;
@@ -86,13 +149,31 @@ impl Artist {
     where
         U: Into<Option<usize>>,
     {
-        let args = Query::with("id", self.id.as_ref())
-            .arg("count", count.into())
+        let args = Query::with("artist", self.name.as_ref())
+            .arg("count", client.page_size(count))
             .build();
 
         let song = client.get("getTopSongs", args).await?;
         Ok(get_list_as!(song, Song))
     }
+
+    /// Returns up to `count` songs to play as a radio station for the artist.
+    ///
+    /// The artist's top songs are interleaved with songs similar to the
+    /// artist (as suggested by last.fm), starting with the most played song.
+    /// Once either list runs out, the rest of the other follows. Songs that
+    /// appear in both lists are only included once.
+    pub async fn radio(&self, client: &Client, count: usize) -> Result<Vec<Song>> {
+        let top = self.top_songs(client, count).await?;
+
+        let args = Query::with("id", self.id.as_ref())
+            .arg("count", count)
+            .build();
+        let song = client.get("getSimilarSongs2", args).await?;
+        let similar = get_list_as!(song, Song);
+
+        Ok(interleave_songs(top, similar, count))
+    }
 }
 
 impl<'de> Deserialize<'de> for Artist {
@@ -105,10 +186,17 @@ impl<'de> Deserialize<'de> for Artist {
         struct _Artist {
             id: String,
             name: String,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
             cover_art: Option<String>,
             album_count: usize,
             #[serde(default)]
             album: Vec<Album>,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+            artist_image_url: Option<String>,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+            starred: Option<String>,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+            music_brainz_id: Option<String>,
         }
 
         let raw = _Artist::deserialize(de)?;
@@ -119,6 +207,9 @@ impl<'de> Deserialize<'de> for Artist {
             cover_id: raw.cover_art,
             album_count: raw.album_count,
             albums: raw.album,
+            artist_image_url: raw.artist_image_url,
+            starred: raw.starred,
+            musicbrainz_id: raw.music_brainz_id,
         })
     }
 }
@@ -191,9 +282,135 @@ impl<'de> Deserialize<'de> for ArtistInfo {
     }
 }
 
+impl Serialize for ArtistInfo {
+    fn serialize<S>(&self, ser: S) -> result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut info = ser.serialize_struct("ArtistInfo", 7)?;
+        info.serialize_field("biography", &self.biography)?;
+        info.serialize_field("musicBrainzId", &self.musicbrainz_id)?;
+        info.serialize_field("lastFmUrl", &self.lastfm_url)?;
+        info.serialize_field("smallImageUrl", &self.image_urls.0)?;
+        info.serialize_field("mediumImageUrl", &self.image_urls.1)?;
+        info.serialize_field("largeImageUrl", &self.image_urls.2)?;
+        info.serialize_field("similarArtist", &self.similar_artists)?;
+        info.end()
+    }
+}
+
+/// Alternates between the two lists of songs, skipping any song already
+/// taken, until `count` songs have been taken.
+fn interleave_songs(first: Vec<Song>, second: Vec<Song>, count: usize) -> Vec<Song> {
+    let mut seen = HashSet::new();
+    let mut songs = Vec::with_capacity(count);
+    let mut first = first.into_iter();
+    let mut second = second.into_iter();
+
+    loop {
+        let (a, b) = (first.next(), second.next());
+        if a.is_none() && b.is_none() {
+            break;
+        }
+        for song in a.into_iter().chain(b) {
+            if songs.len() < count && seen.insert(song.id.clone()) {
+                songs.push(song);
+            }
+        }
+        if songs.len() >= count {
+            break;
+        }
+    }
+
+    songs
+}
+
+/// Groups artists under the first letter of their name, skipping any leading
+/// article in `articles`.
+///
+/// Names that don't start with a letter are grouped under `#`, which comes
+/// first; the other groups are in alphabetical order. Accented Latin letters
+/// are grouped and sorted with their base letter, so "Ángel" is under `A`.
+/// Within a group, artists are sorted by name, ignoring case, accents and the
+/// article.
+pub(crate) fn bucket_artists(
+    artists: Vec<Artist>,
+    articles: &[String],
+) -> Vec<(String, Vec<Artist>)> {
+    let mut keyed = artists
+        .into_iter()
+        .map(|a| {
+            let key = strip_article(&a.name, articles)
+                .to_lowercase()
+                .chars()
+                .map(fold_accent)
+                .collect::<String>();
+            (key, a)
+        })
+        .collect::<Vec<_>>();
+    keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut buckets: Vec<(String, Vec<Artist>)> = Vec::new();
+    for (key, artist) in keyed {
+        let label = match key.chars().next() {
+            Some(c) if c.is_alphabetic() => c.to_uppercase().collect(),
+            _ => "#".to_string(),
+        };
+        match buckets.iter_mut().find(|(existing, _)| *existing == label) {
+            Some((_, group)) => group.push(artist),
+            None => buckets.push((label, vec![artist])),
+        }
+    }
+    buckets.sort_by(|(a, _), (b, _)| (a != "#", a).cmp(&(b != "#", b)));
+    buckets
+}
+
+/// Returns the base letter of an accented lowercase Latin letter, or the
+/// letter itself.
+fn fold_accent(c: char) -> char {
+    match c {
+        'à'..='å' | 'ā' | 'ă' | 'ą' => 'a',
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => 'c',
+        'ď' | 'đ' => 'd',
+        'è'..='ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => 'e',
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => 'g',
+        'ĥ' | 'ħ' => 'h',
+        'ì'..='ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => 'i',
+        'ĵ' => 'j',
+        'ķ' => 'k',
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => 'l',
+        'ñ' | 'ń' | 'ņ' | 'ň' => 'n',
+        'ò'..='ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => 'o',
+        'ŕ' | 'ŗ' | 'ř' => 'r',
+        'ś' | 'ŝ' | 'ş' | 'š' => 's',
+        'ţ' | 'ť' | 'ŧ' => 't',
+        'ù'..='ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => 'u',
+        'ŵ' => 'w',
+        'ý' | 'ÿ' | 'ŷ' => 'y',
+        'ź' | 'ż' | 'ž' => 'z',
+        c => c,
+    }
+}
+
+/// Drops a leading article, and the space after it, from the name. Ignores
+/// case when matching the article.
+fn strip_article<'a>(name: &'a str, articles: &[String]) -> &'a str {
+    for article in articles {
+        let len = article.len();
+        if name.len() > len
+            && name.is_char_boundary(len)
+            && name[..len].eq_ignore_ascii_case(article)
+            && name[len..].starts_with(' ')
+        {
+            return name[len..].trim_start();
+        }
+    }
+    name
+}
+
 /// Fetches an artist from the Subsonic server.
 async fn get_artist(client: &Client, id: &str) -> Result<Artist> {
-    let res = client.get("getArtist", Query::with("id", id)).await?;
+    let res = client.get_entity("getArtist", id).await?;
     Ok(serde_json::from_value::<Artist>(res)?)
 }
 
@@ -202,6 +419,55 @@ mod tests {
     use super::*;
     use crate::test_util;
 
+    #[test]
+    fn bucket_by_letter() {
+        let artist = |name: &str| {
+            serde_json::from_value::<Artist>(serde_json::json!({
+                "id": name,
+                "name": name,
+                "albumCount": 0,
+            }))
+            .unwrap()
+        };
+        let artists = [
+            "the Beatles",
+            "ABBA",
+            "Los Lobos",
+            "2Pac",
+            "Björk",
+            "The",
+            "álvaro",
+        ]
+        .iter()
+        .map(|n| artist(n))
+        .collect();
+        let articles = vec!["The".to_string(), "Los".to_string()];
+
+        let buckets = bucket_artists(artists, &articles)
+            .into_iter()
+            .map(|(label, group)| {
+                let names = group.into_iter().map(|a| a.name).collect::<Vec<_>>();
+                (label, names)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            buckets,
+            [
+                ("#".to_string(), vec!["2Pac".to_string()]),
+                (
+                    "A".to_string(),
+                    vec!["ABBA".to_string(), "álvaro".to_string()]
+                ),
+                (
+                    "B".to_string(),
+                    vec!["the Beatles".to_string(), "Björk".to_string()]
+                ),
+                ("L".to_string(), vec!["Los Lobos".to_string()]),
+                ("T".to_string(), vec!["The".to_string()]),
+            ]
+        );
+    }
+
     #[test]
     fn parse_artist() {
         let parsed = serde_json::from_value::<Artist>(raw()).unwrap();
@@ -221,6 +487,76 @@ mod tests {
         assert_eq!(parsed.albums[0].song_count, 9);
     }
 
+    #[test]
+    fn parse_artist_art_and_stars() {
+        let parsed = serde_json::from_value::<Artist>(raw()).unwrap();
+
+        assert_eq!(parsed.cover_id(), Some("ar-1"));
+        assert_eq!(
+            parsed.artist_image_url.as_deref(),
+            Some("https://lastfm.example/300x300/1.png")
+        );
+        assert_eq!(parsed.starred.as_deref(), Some("2017-06-01T19:48:25.635Z"));
+        assert_eq!(
+            parsed.albums[0].starred.as_deref(),
+            Some("2017-08-27T07:52:23.926Z")
+        );
+
+        let mut raw = raw();
+        raw["starred"] = "".into();
+        raw["album"][0].as_object_mut().unwrap().remove("starred");
+        let parsed = serde_json::from_value::<Artist>(raw).unwrap();
+        assert!(parsed.starred.is_none());
+        assert!(parsed.albums[0].starred.is_none());
+    }
+
+    #[test]
+    fn artist_info_image_urls() {
+        let info = serde_json::from_str::<ArtistInfo>(
+            r#"{
+            "biography" : "",
+            "musicBrainzId" : "",
+            "lastFmUrl" : "",
+            "smallImageUrl" : "https://lastfm.example/34s/1.png",
+            "mediumImageUrl" : "https://lastfm.example/64s/1.png",
+            "largeImageUrl" : "",
+            "similarArtist" : [ ]
+        }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            info.image_url(ImageSize::Medium),
+            Some("https://lastfm.example/64s/1.png")
+        );
+        assert_eq!(info.image_url(ImageSize::Large), None);
+    }
+
+    #[test]
+    fn interleave_radio_songs() {
+        let song = |id: &str| {
+            serde_json::from_value::<Song>(serde_json::json!({
+                "id" : id,
+                "title" : "Bellevue Avenue",
+                "size" : 5400185,
+                "contentType" : "audio/mpeg",
+                "suffix" : "mp3",
+                "path" : "Misteur Valaire/Bellevue/01 - Misteur Valaire - Bellevue Avenue.mp3",
+                "type" : "music"
+            }))
+            .unwrap()
+        };
+        let top = vec![song("1"), song("2"), song("3"), song("4")];
+        let similar = vec![song("2"), song("7")];
+
+        let ids = |songs: Vec<Song>| songs.into_iter().map(|s| s.id.clone()).collect::<Vec<_>>();
+        assert_eq!(
+            ids(interleave_songs(top.clone(), similar.clone(), 10)),
+            vec!["1", "2", "7", "3", "4"]
+        );
+        assert_eq!(ids(interleave_songs(top, similar, 2)), vec!["1", "2"]);
+    }
+
     #[test]
     fn remote_artist_album_list() {
         let srv = test_util::demo_site().unwrap();
@@ -248,7 +584,9 @@ mod tests {
             "id" : "1",
             "name" : "Misteur Valaire",
             "coverArt" : "ar-1",
+            "artistImageUrl" : "https://lastfm.example/300x300/1.png",
             "albumCount" : 1,
+            "starred" : "2017-06-01T19:48:25.635Z",
             "album" : [ {
                 "id" : "1",
                 "name" : "Bellevue",
@@ -259,6 +597,7 @@ mod tests {
                 "duration" : 1920,
                 "playCount" : 2223,
                 "created" : "2017-03-12T11:07:25.000Z",
+                "starred" : "2017-08-27T07:52:23.926Z",
                 "genre" : "(255)"
             } ]
         }"#,
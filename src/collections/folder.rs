@@ -0,0 +1,115 @@
+//! Browsing scoped to one music folder.
+
+use crate::search::{SearchPage, SearchResult};
+use crate::{Album, Artist, Client, IndexArtist, Indexes, ListType, Result, Song};
+
+/// A view of a `Client` limited to one music folder.
+///
+/// Created with [`Client::scoped_to_folder`]. Every method passes the folder
+/// along, saving it from being threaded through each call on servers with
+/// several music folders.
+///
+/// # Examples
+///
+/// ```no_run
+/// use sunk::{Client, ListType};
+/// use sunk::search::SearchPage;
+///
+/// # async fn run() -> sunk::Result<()> {
+/// # let client = Client::new("http://demo.subsonic.org", "guest3", "guest")?;
+/// let audiobooks = client.scoped_to_folder(3);
+/// let newest = audiobooks
+///     .albums(ListType::RecentlyAdded, SearchPage::new())
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Client::scoped_to_folder`]: ../../struct.Client.html#method.scoped_to_folder
+#[derive(Debug, Clone, Copy)]
+pub struct FolderScope<'a> {
+    client: &'a Client,
+    folder: usize,
+}
+
+impl<'a> FolderScope<'a> {
+    pub(crate) fn new(client: &'a Client, folder: usize) -> FolderScope<'a> {
+        FolderScope { client, folder }
+    }
+
+    /// Returns the ID of the music folder the view is limited to.
+    pub fn folder(&self) -> usize {
+        self.folder
+    }
+
+    /// Returns the client behind the view.
+    pub fn client(&self) -> &'a Client {
+        self.client
+    }
+
+    /// Returns the folder-based index of artists in the folder.
+    pub async fn indexes(&self) -> Result<Indexes> {
+        self.client.indexes(self.folder).await
+    }
+
+    /// Returns the folder-based index of artists in the folder if it has
+    /// changed since the given time. See [`Client::indexes_if_modified`].
+    ///
+    /// [`Client::indexes_if_modified`]: ../../struct.Client.html#method.indexes_if_modified
+    pub async fn indexes_if_modified(&self, since: u64) -> Result<Option<Indexes>> {
+        self.client.indexes_if_modified(since, self.folder).await
+    }
+
+    /// Calls `f` with each artist in the folder's index, one at a time.
+    pub async fn for_each_index_artist<F>(&self, f: F) -> Result<()>
+    where
+        F: FnMut(IndexArtist),
+    {
+        self.client.for_each_index_artist(self.folder, f).await
+    }
+
+    /// Calls `f` with each artist in the folder, organised by ID3 tags, one
+    /// at a time.
+    pub async fn for_each_artist<F>(&self, f: F) -> Result<()>
+    where
+        F: FnMut(Artist),
+    {
+        self.client.for_each_artist(self.folder, f).await
+    }
+
+    /// Lists the albums in the folder. Supports paging.
+    pub async fn albums(&self, list_type: ListType, page: SearchPage) -> Result<Vec<Album>> {
+        Album::list(self.client, list_type, page, self.folder).await
+    }
+
+    /// Returns the albums added to the folder after `since`, newest first.
+    /// See [`Album::added_since`].
+    ///
+    /// [`Album::added_since`]: ../album/struct.Album.html#method.added_since
+    pub async fn albums_added_since(&self, since: &str) -> Result<Vec<Album>> {
+        Album::added_since(self.client, since, self.folder).await
+    }
+
+    /// Lists the songs in the folder in a genre. Supports paging.
+    pub async fn songs_in_genre(&self, genre: &str, page: SearchPage) -> Result<Vec<Song>> {
+        Song::list_in_genre(self.client, genre, page, self.folder as u64).await
+    }
+
+    /// Searches for artists, albums, and songs in the folder.
+    pub async fn search(
+        &self,
+        query: &str,
+        artist_page: SearchPage,
+        album_page: SearchPage,
+        song_page: SearchPage,
+    ) -> Result<SearchResult> {
+        self.client
+            .search_in_folders(query, artist_page, album_page, song_page, &[self.folder])
+            .await
+    }
+
+    /// Returns the starred artists, albums, and songs in the folder.
+    pub async fn starred(&self) -> Result<SearchResult> {
+        self.client.starred(self.folder).await
+    }
+}
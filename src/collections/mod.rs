@@ -1,24 +1,33 @@
 //! Collections management APIs.
 
+use std::cmp::Reverse;
+use std::collections::HashMap;
 use std::result;
 
 use serde::de::{Deserialize, Deserializer};
+use serde::ser::Serializer;
+
+use crate::Result;
 
 pub mod album;
 pub mod artist;
+pub mod folder;
 pub mod playlist;
 
 pub use self::album::{Album, AlbumInfo, ListType};
-pub use self::artist::{Artist, ArtistInfo};
+pub use self::artist::{Artist, ArtistInfo, ImageSize};
+pub use self::folder::FolderScope;
 pub use self::playlist::Playlist;
 
 /// A representation of a music folder on a Subsonic server.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct MusicFolder {
     /// The index number of the folder.
+    #[serde(serialize_with = "crate::util::serialize_display")]
     pub id: usize,
     /// The name assigned to the folder.
     pub name: String,
+    #[serde(skip)]
     _private: bool,
 }
 
@@ -42,16 +51,239 @@ impl<'de> Deserialize<'de> for MusicFolder {
     }
 }
 
+/// The folder-based index of artists on a Subsonic server, from `getIndexes`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Indexes {
+    /// When the index last changed, in milliseconds since the Unix epoch.
+    pub last_modified: u64,
+    /// Articles that are ignored when sorting artists into the index.
+    #[serde(serialize_with = "serialize_articles")]
+    pub ignored_articles: Vec<String>,
+    /// Artists the server suggests as shortcuts.
+    #[serde(rename = "shortcut")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub shortcuts: Vec<IndexArtist>,
+    /// The artists, grouped by the first letter of their name.
+    #[serde(rename = "index")]
+    pub indexes: Vec<Index>,
+}
+
+/// A group of artists in an [`Indexes`](./struct.Indexes.html).
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Index {
+    /// The name of the group; typically a letter.
+    pub name: String,
+    /// The artists in the group.
+    #[serde(rename = "artist")]
+    #[serde(default)]
+    pub artists: Vec<IndexArtist>,
+}
+
+/// An artist folder listed in an [`Indexes`](./struct.Indexes.html).
+#[derive(Debug, Deserialize, Serialize)]
+pub struct IndexArtist {
+    /// The ID of the artist's folder.
+    pub id: String,
+    /// The name of the artist.
+    pub name: String,
+}
+
+impl<'de> Deserialize<'de> for Indexes {
+    fn deserialize<D>(de: D) -> result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct _Indexes {
+            last_modified: u64,
+            #[serde(default)]
+            ignored_articles: String,
+            #[serde(default)]
+            shortcut: Vec<IndexArtist>,
+            #[serde(default)]
+            index: Vec<Index>,
+        }
+
+        let raw = _Indexes::deserialize(de)?;
+        Ok(Indexes {
+            last_modified: raw.last_modified,
+            ignored_articles: raw
+                .ignored_articles
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+            shortcuts: raw.shortcut,
+            indexes: raw.index,
+        })
+    }
+}
+
+fn serialize_articles<S: Serializer>(
+    articles: &[String],
+    ser: S,
+) -> result::Result<S::Ok, S::Error> {
+    ser.serialize_str(&articles.join(" "))
+}
+
+/// Parses a `getIndexes` response, returning `None` if the server left out
+/// the index because nothing has changed.
+pub(crate) fn indexes_if_modified(res: serde_json::Value) -> Result<Option<Indexes>> {
+    if res.get("index").is_none() && res.get("shortcut").is_none() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_value(res)?))
+}
+
 /// A genre contained on a Subsonic server.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Genre {
     /// The name of the genre.
+    #[serde(rename = "value")]
+    #[serde(alias = "name")]
     pub name: String,
     /// The number of songs in the genre.
     pub song_count: u64,
     /// The number of albums in the genre.
     pub album_count: u64,
     #[serde(default)]
+    #[serde(skip_serializing)]
     _private: bool,
 }
+
+/// Orderings that [`Client::genres`] can return genres in.
+///
+/// [`Client::genres`]: ../struct.Client.html#method.genres
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenreSort {
+    /// Alphabetically by name, ignoring case.
+    Name,
+    /// By number of songs, most first.
+    SongCount,
+    /// By number of albums, most first.
+    AlbumCount,
+}
+
+/// Merges genres whose names only differ in case, and optionally sorts them.
+///
+/// Merged genres keep the spelling with the most songs, and the song and album
+/// counts of all spellings.
+pub(crate) fn tidy_genres(genres: Vec<Genre>, sort: Option<GenreSort>) -> Vec<Genre> {
+    let mut tidy: Vec<Genre> = Vec::with_capacity(genres.len());
+    let mut seen = HashMap::new();
+    for genre in genres {
+        let key = genre.name.to_lowercase();
+        // Spellings compete on their own song counts, not on the totals
+        // merged so far.
+        match seen.get_mut(&key) {
+            Some((i, most)) => {
+                let kept: &mut Genre = &mut tidy[*i];
+                if genre.song_count > *most {
+                    *most = genre.song_count;
+                    kept.name = genre.name;
+                }
+                kept.song_count += genre.song_count;
+                kept.album_count += genre.album_count;
+            }
+            None => {
+                seen.insert(key, (tidy.len(), genre.song_count));
+                tidy.push(genre);
+            }
+        }
+    }
+
+    match sort {
+        Some(GenreSort::Name) => tidy.sort_by_key(|g| g.name.to_lowercase()),
+        Some(GenreSort::SongCount) => tidy.sort_by_key(|g| Reverse(g.song_count)),
+        Some(GenreSort::AlbumCount) => tidy.sort_by_key(|g| Reverse(g.album_count)),
+        None => {}
+    }
+    tidy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn genres() -> Vec<Genre> {
+        serde_json::from_str(
+            r#"[
+            { "songCount" : 12, "albumCount" : 2, "value" : "rock" },
+            { "songCount" : 30, "albumCount" : 3, "value" : "Jazz" },
+            { "songCount" : 40, "albumCount" : 1, "value" : "Rock" }
+        ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn merge_genres_by_case() {
+        let tidy = tidy_genres(genres(), None);
+
+        assert_eq!(tidy.len(), 2);
+        assert_eq!(tidy[0].name, "Rock");
+        assert_eq!(tidy[0].song_count, 52);
+        assert_eq!(tidy[0].album_count, 3);
+    }
+
+    #[test]
+    fn merge_genres_by_own_count() {
+        let genres: Vec<Genre> = serde_json::from_str(
+            r#"[
+            { "songCount" : 10, "albumCount" : 1, "value" : "rock" },
+            { "songCount" : 8, "albumCount" : 1, "value" : "ROCK" },
+            { "songCount" : 15, "albumCount" : 2, "value" : "Rock" }
+        ]"#,
+        )
+        .unwrap();
+        let tidy = tidy_genres(genres, None);
+
+        assert_eq!(tidy.len(), 1);
+        assert_eq!(tidy[0].name, "Rock");
+        assert_eq!(tidy[0].song_count, 33);
+        assert_eq!(tidy[0].album_count, 4);
+    }
+
+    #[test]
+    fn parse_modified_indexes() {
+        let res = serde_json::from_str(
+            r#"{
+            "lastModified" : 1518134400000,
+            "ignoredArticles" : "The El La Los Las Le Les",
+            "index" : [ {
+                "name" : "M",
+                "artist" : [ { "id" : "5", "name" : "Misteur Valaire" } ]
+            } ]
+        }"#,
+        )
+        .unwrap();
+        let indexes = indexes_if_modified(res).unwrap().unwrap();
+
+        assert_eq!(indexes.ignored_articles.len(), 7);
+        assert_eq!(indexes.indexes[0].artists[0].name, "Misteur Valaire");
+    }
+
+    #[test]
+    fn parse_unmodified_indexes() {
+        let res = serde_json::from_str(
+            r#"{
+            "lastModified" : 1518134400000,
+            "ignoredArticles" : "The El La Los Las Le Les"
+        }"#,
+        )
+        .unwrap();
+
+        assert!(indexes_if_modified(res).unwrap().is_none());
+    }
+
+    #[test]
+    fn sort_genres() {
+        let by_name = tidy_genres(genres(), Some(GenreSort::Name));
+        assert_eq!(by_name[0].name, "Jazz");
+
+        let by_albums = tidy_genres(genres(), Some(GenreSort::AlbumCount));
+        assert_eq!(by_albums[0].name, "Rock");
+    }
+}
@@ -6,17 +6,22 @@ use serde::de::{Deserialize, Deserializer};
 use serde_json;
 
 use crate::query::Query;
-use crate::{Client, Error, Media, Result, Song};
+use crate::{ApiError, Client, Error, Media, Result, Song};
 
 #[allow(missing_docs)]
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 #[readonly::make]
 pub struct Playlist {
+    #[serde(serialize_with = "crate::util::serialize_display")]
     pub id: u64,
     pub name: String,
     pub duration: u64,
+    #[serde(rename = "coverArt")]
     pub cover_id: String,
     pub song_count: u64,
+    #[serde(rename = "entry")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub songs: Vec<Song>,
 }
 
@@ -29,6 +34,101 @@ impl Playlist {
             Ok(self.songs.clone())
         }
     }
+
+    /// Fetches every song in the playlist, following up on servers that
+    /// truncate large playlists.
+    ///
+    /// If fewer songs than the playlist's `song_count` come back, the rest
+    /// are requested from an offset. Not every server supports that; if the
+    /// server ignores the offset, a warning is logged and the songs received
+    /// so far are returned.
+    pub async fn all_songs(&self, client: &Client) -> Result<Vec<Song>> {
+        let mut songs = self.songs(client).await?;
+        while (songs.len() as u64) < self.song_count {
+            let args = Query::with("id", self.id)
+                .arg("offset", songs.len())
+                .build();
+            let res = client.get("getPlaylist", args).await?;
+            let page = serde_json::from_value::<Playlist>(res)?.songs;
+            if !append_page(&mut songs, page) {
+                warn!(
+                    "Playlist {} was truncated to {} of {} songs",
+                    self.id,
+                    songs.len(),
+                    self.song_count
+                );
+                break;
+            }
+        }
+        Ok(songs)
+    }
+
+    /// Exports the playlist as JSON, to back it up or move it to another
+    /// server with [`import_playlist`].
+    ///
+    /// The export holds the playlist's name, and the ID, title, artist,
+    /// album, and duration of each song.
+    ///
+    /// [`import_playlist`]: ./fn.import_playlist.html
+    pub async fn export_json(&self, client: &Client) -> Result<String> {
+        let songs = self.all_songs(client).await?;
+        Ok(serde_json::to_string_pretty(&Export::new(
+            &self.name, &songs,
+        ))?)
+    }
+}
+
+/// A playlist saved with [`Playlist::export_json`].
+///
+/// [`Playlist::export_json`]: ./struct.Playlist.html#method.export_json
+#[derive(Debug, Serialize, Deserialize)]
+struct Export {
+    name: String,
+    songs: Vec<ExportedSong>,
+}
+
+/// A song in an exported playlist. Only the ID is needed to import it; the
+/// rest describes the song to anyone reading the export.
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedSong {
+    id: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    artist: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    album: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    duration: Option<u64>,
+}
+
+impl Export {
+    fn new(name: &str, songs: &[Song]) -> Export {
+        Export {
+            name: name.to_string(),
+            songs: songs
+                .iter()
+                .map(|s| ExportedSong {
+                    id: s.id.clone(),
+                    title: s.title.clone(),
+                    artist: s.artist.clone(),
+                    album: s.album.clone(),
+                    duration: s.duration,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Adds the next page of a playlist's songs, returning whether it continued
+/// the playlist.
+fn append_page(songs: &mut Vec<Song>, page: Vec<Song>) -> bool {
+    // A server that ignores the offset sends the start of the playlist again.
+    if page.is_empty() || page.first().map(|s| &s.id) == songs.first().map(|s| &s.id) {
+        return false;
+    }
+    songs.extend(page);
+    true
 }
 
 impl<'de> Deserialize<'de> for Playlist {
@@ -50,6 +150,7 @@ impl<'de> Deserialize<'de> for Playlist {
             // changed: String,
             cover_art: String,
             #[serde(default)]
+            #[serde(alias = "entry")]
             songs: Vec<Song>,
         }
 
@@ -133,6 +234,40 @@ pub async fn create_playlist(
     // }
 }
 
+/// Creates a playlist from one exported with [`Playlist::export_json`].
+///
+/// The playlist is named `name`, or the exported name if not given. Songs
+/// that no longer exist on the server are left out; their IDs are returned
+/// alongside the new playlist.
+///
+/// [`Playlist::export_json`]: ./struct.Playlist.html#method.export_json
+pub async fn import_playlist<'a, S>(
+    client: &Client,
+    name: S,
+    exported: &str,
+) -> Result<(Playlist, Vec<String>)>
+where
+    S: Into<Option<&'a str>>,
+{
+    let export = serde_json::from_str::<Export>(exported)?;
+
+    let mut found = Vec::with_capacity(export.songs.len());
+    let mut skipped = Vec::new();
+    for song in export.songs {
+        match Song::get(client, &song.id).await {
+            Ok(_) => found.push(song.id),
+            Err(Error::Api(ApiError::NotFound, _)) => skipped.push(song.id),
+            Err(e) => return Err(e),
+        }
+    }
+
+    let args = Query::with("name", name.into().unwrap_or(&export.name))
+        .arg_list("songId", &found)
+        .build();
+    let res = client.get("createPlaylist", args).await?;
+    Ok((serde_json::from_value(res)?, skipped))
+}
+
 /// Updates a playlist. Only the owner of the playlist is privileged to do so.
 pub async fn update_playlist<'a, B, S>(
     client: &Client,
@@ -181,11 +316,78 @@ mod tests {
         assert!(matches!(
             songs,
             Err(crate::error::Error::Api(
-                crate::error::ApiError::NotAuthorized(_)
+                crate::error::ApiError::NotAuthorized(_),
+                _
             ))
         ));
     }
 
+    #[test]
+    fn serialize_playlist_id_as_string() {
+        let parsed = serde_json::from_value::<Playlist>(raw()).unwrap();
+        let value = serde_json::to_value(&parsed).unwrap();
+
+        assert_eq!(value["id"], "1");
+        assert_eq!(value["coverArt"], "pl-2");
+        assert_eq!(serde_json::from_value::<Playlist>(value).unwrap().id, 1);
+    }
+
+    #[test]
+    fn append_playlist_pages() {
+        let song = |id: &str| {
+            serde_json::from_value::<Song>(serde_json::json!({
+                "id": id,
+                "title": "Song",
+                "size": 1024,
+                "contentType": "audio/mpeg",
+                "suffix": "mp3",
+                "type": "music"
+            }))
+            .unwrap()
+        };
+
+        let mut songs = vec![song("1"), song("2")];
+        assert!(append_page(&mut songs, vec![song("3")]));
+        assert_eq!(songs.len(), 3);
+
+        assert!(!append_page(&mut songs, vec![song("1"), song("2")]));
+        assert!(!append_page(&mut songs, Vec::new()));
+        assert_eq!(songs.len(), 3);
+    }
+
+    #[test]
+    fn export_playlist_songs() {
+        let song = serde_json::from_value::<Song>(serde_json::json!({
+            "id": "27",
+            "title": "Bellevue Avenue",
+            "artist": "Misteur Valaire",
+            "size": 1024,
+            "contentType": "audio/mpeg",
+            "suffix": "mp3",
+            "duration": 198,
+            "type": "music"
+        }))
+        .unwrap();
+
+        let export = serde_json::to_value(Export::new("Sleep Hits", &[song])).unwrap();
+        assert_eq!(
+            export,
+            serde_json::json!({
+                "name": "Sleep Hits",
+                "songs": [ {
+                    "id": "27",
+                    "title": "Bellevue Avenue",
+                    "artist": "Misteur Valaire",
+                    "duration": 198
+                } ]
+            })
+        );
+
+        let minimal = r#"{ "name": "Sleep Hits", "songs": [ { "id": "27" } ] }"#;
+        let parsed = serde_json::from_str::<Export>(minimal).unwrap();
+        assert_eq!(parsed.songs[0].id, "27");
+    }
+
     fn raw() -> serde_json::Value {
         serde_json::from_str(
             r#"{
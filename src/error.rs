@@ -15,9 +15,10 @@ pub enum Error {
     /// Unable to recognize the URL provided in `Client` setup.
     #[error("Invalid URL: {}", _0)]
     Url(#[from] UrlError),
-    /// The Subsonic server returned an error.
-    #[error("{}", _0)]
-    Api(#[from] ApiError),
+    /// The Subsonic server returned an error, along with a link to more
+    /// information about it if the server gave one.
+    #[error("{}{}", _0, see_also(_1))]
+    Api(ApiError, Option<String>),
 
     /// A number conversion errored.
     #[error("Failed to parse int: {}", _0)]
@@ -32,11 +33,61 @@ pub enum Error {
     #[error("Error serialising: {}", _0)]
     Serde(#[from] serde_json::Error),
 
+    /// The server responded with an HTML page, usually from a reverse proxy
+    /// asking to log in or reporting that the server is down. Holds the
+    /// status of the response.
+    #[error("received HTML instead of Subsonic response, status {}", _0)]
+    Html(reqwest::StatusCode),
+
+    /// The server responded with something other than a Subsonic response,
+    /// such as a login page from a reverse proxy. Holds the start of the
+    /// response body.
+    #[error("Not a Subsonic response; got: {}", _0)]
+    InvalidResponse(String),
+
+    /// The server doesn't support a feature the method relies on. Holds the
+    /// name of the missing feature.
+    #[error("Server does not support {}", _0)]
+    UnsupportedApi(&'static str),
+
     /// For general, one-off errors.
     #[error("{}", _0)]
     Other(&'static str),
 }
 
+impl Error {
+    /// Returns the link to more information about an API error, if the server
+    /// sent one.
+    pub fn help_url(&self) -> Option<&str> {
+        match *self {
+            Error::Api(_, ref url) => url.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the server refused the request because its Subsonic
+    /// trial has expired.
+    ///
+    /// The server's REST API stays disabled until a Subsonic Premium license
+    /// is bought, so retrying won't help; the server's owner has to act.
+    pub fn is_trial_expired(&self) -> bool {
+        matches!(*self, Error::Api(ApiError::TrialExpired, _))
+    }
+}
+
+impl From<ApiError> for Error {
+    fn from(err: ApiError) -> Error {
+        Error::Api(err, None)
+    }
+}
+
+fn see_also(help_url: &Option<String>) -> String {
+    match *help_url {
+        Some(ref url) => format!(" (see: {url})"),
+        None => String::new(),
+    }
+}
+
 /// Possible errors when initializing a `Client`.
 #[derive(Debug, thiserror::Error)]
 pub enum UrlError {
@@ -129,7 +180,7 @@ impl<'de> Deserialize<'de> for ApiError {
         use self::ApiError::*;
 
         match raw.code {
-            10 => Ok(Generic(raw.message)),
+            10 => Ok(MissingParameter),
             20 => Ok(ClientMustUpgrade),
             30 => Ok(ServerMustUpgrade),
             40 => Ok(WrongAuth),
@@ -137,7 +188,8 @@ impl<'de> Deserialize<'de> for ApiError {
             50 => Ok(NotAuthorized(raw.message)),
             60 => Ok(TrialExpired),
             70 => Ok(NotFound),
-            _ => unimplemented!(),
+            // Code 0, as well as any code added after 1.14.0.
+            _ => Ok(Generic(raw.message)),
         }
     }
 }
@@ -153,8 +205,61 @@ impl fmt::Display for ApiError {
             WrongAuth => write!(f, "Wrong username or password"),
             Ldap => write!(f, "Token authentication not supported for LDAP users"),
             NotAuthorized(ref s) => write!(f, "Not authorized: {s}"),
-            TrialExpired => write!(f, "Subsonic trial period has expired"),
+            TrialExpired => write!(
+                f,
+                "Subsonic trial period has expired; the server needs a Subsonic Premium license"
+            ),
             NotFound => write!(f, "Requested data not found"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(code: u16) -> ApiError {
+        let raw = format!(r#"{{ "code": {code}, "message": "Permission denied" }}"#);
+        serde_json::from_str(&raw).unwrap()
+    }
+
+    #[test]
+    fn parse_api_error_codes() {
+        for code in &[10, 20, 30, 40, 41, 50, 60, 70] {
+            assert_eq!(parse(*code).as_u16(), *code);
+        }
+
+        match parse(50) {
+            ApiError::NotAuthorized(msg) => assert_eq!(msg, "Permission denied"),
+            e => panic!("unexpected error: {e}"),
+        }
+    }
+
+    #[test]
+    fn parse_unknown_api_error() {
+        match parse(99) {
+            ApiError::Generic(msg) => assert_eq!(msg, "Permission denied"),
+            e => panic!("unexpected error: {e}"),
+        }
+    }
+
+    #[test]
+    fn detect_trial_expired() {
+        let err = Error::from(parse(60));
+        assert!(err.is_trial_expired());
+        assert!(err.to_string().contains("Subsonic Premium license"));
+        assert!(!Error::from(parse(70)).is_trial_expired());
+        assert!(!Error::Other("trial").is_trial_expired());
+    }
+
+    #[test]
+    fn display_help_url() {
+        let err = Error::Api(ApiError::NotFound, Some("https://example.com/help".into()));
+        assert_eq!(err.help_url(), Some("https://example.com/help"));
+        assert_eq!(
+            err.to_string(),
+            "Requested data not found (see: https://example.com/help)"
+        );
+        assert_eq!(Error::from(ApiError::NotFound).help_url(), None);
+    }
+}
@@ -1,7 +1,9 @@
 //! Jukebox management and control APIs.
 
 use std::result;
+use std::time::Duration;
 
+use futures_util::stream::{self, Stream};
 use serde::de::{Deserialize, Deserializer};
 use serde_json;
 
@@ -18,7 +20,7 @@ pub struct Jukebox<'a> {
 }
 
 /// A representation of the jukebox's current status.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct JukeboxStatus {
     /// Current index in the playlist (zero-indexed). `-1` means that the
     /// jukebox has had its playlist cleared and has not since been played.
@@ -35,11 +37,13 @@ pub struct JukeboxStatus {
 
 /// A more detailed representation of the jukebox's status. Includes its
 /// current playlist.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct JukeboxPlaylist {
     /// The jukebox's status.
+    #[serde(flatten)]
     pub status: JukeboxStatus,
     /// The jukebox's current playlist.
+    #[serde(rename = "entry")]
     pub songs: Vec<Song>,
 }
 
@@ -55,6 +59,7 @@ impl<'de> Deserialize<'de> for JukeboxPlaylist {
             playing: bool,
             gain: f32,
             position: usize,
+            #[serde(default)]
             entry: Vec<Song>,
         }
         let raw = _Playlist::deserialize(de)?;
@@ -70,57 +75,164 @@ impl<'de> Deserialize<'de> for JukeboxPlaylist {
     }
 }
 
+/// An action for the jukebox to take, sent with [`Jukebox::control`].
+///
+/// [`Jukebox::control`]: ./struct.Jukebox.html#method.control
+#[derive(Debug, Clone, PartialEq)]
+pub enum JukeboxAction {
+    /// Returns the jukebox's playlist along with its status.
+    Get,
+    /// Returns the jukebox's status.
+    Status,
+    /// Starts playback.
+    Start,
+    /// Pauses playback.
+    Stop,
+    /// Moves playback to the song at the index (zero-indexed).
+    Skip(usize),
+    /// Adds the songs with the IDs to the end of the playlist.
+    Add(Vec<String>),
+    /// Replaces the playlist with the songs with the IDs.
+    Set(Vec<String>),
+    /// Clears the playlist.
+    Clear,
+    /// Removes the song at the index from the playlist.
+    Remove(usize),
+    /// Shuffles the playlist.
+    Shuffle,
+    /// Sets the volume, from `0` to `1.0`.
+    SetGain(f32),
+}
+
+impl JukeboxAction {
+    fn to_query(&self) -> Query {
+        use self::JukeboxAction::*;
+        match *self {
+            Get => Query::with("action", "get"),
+            Status => Query::with("action", "status"),
+            Start => Query::with("action", "start"),
+            Stop => Query::with("action", "stop"),
+            Skip(index) => Query::with("action", "skip").arg("index", index).build(),
+            Add(ref ids) => Query::with("action", "add").arg_list("id", ids).build(),
+            Set(ref ids) => Query::with("action", "set").arg_list("id", ids).build(),
+            Clear => Query::with("action", "clear"),
+            Remove(index) => Query::with("action", "remove").arg("index", index).build(),
+            Shuffle => Query::with("action", "shuffle"),
+            SetGain(gain) => Query::with("action", "setGain").arg("gain", gain).build(),
+        }
+    }
+}
+
+/// The jukebox's answer to a [`JukeboxAction`].
+///
+/// [`JukeboxAction::Get`] is answered with the playlist; every other action
+/// with the status alone.
+///
+/// [`JukeboxAction`]: ./enum.JukeboxAction.html
+/// [`JukeboxAction::Get`]: ./enum.JukeboxAction.html#variant.Get
+#[derive(Debug)]
+pub enum JukeboxResponse {
+    /// The jukebox's status.
+    Status(JukeboxStatus),
+    /// The jukebox's playlist and status.
+    Playlist(JukeboxPlaylist),
+}
+
+impl JukeboxResponse {
+    /// Returns the jukebox's status, which every response carries.
+    pub fn into_status(self) -> JukeboxStatus {
+        match self {
+            JukeboxResponse::Status(status) => status,
+            JukeboxResponse::Playlist(playlist) => playlist.status,
+        }
+    }
+
+    fn parse(action: &JukeboxAction, res: serde_json::Value) -> Result<JukeboxResponse> {
+        Ok(match *action {
+            JukeboxAction::Get => JukeboxResponse::Playlist(serde_json::from_value(res)?),
+            _ => JukeboxResponse::Status(serde_json::from_value(res)?),
+        })
+    }
+}
+
 impl<'a> Jukebox<'a> {
     /// Creates a new handler to the jukebox of the client.
-    pub async fn start(client: &'a Client) -> Jukebox {
+    pub async fn start(client: &'a Client) -> Jukebox<'a> {
         Jukebox { client }
     }
 
-    async fn send_action_with<U>(
-        &self,
-        action: &str,
-        index: U,
-        ids: &[&str],
-    ) -> Result<JukeboxStatus>
-    where
-        U: Into<Option<usize>>,
-    {
-        let args = Query::with("action", action)
-            .arg("index", index.into())
-            .arg_list("id", ids)
-            .build();
-        let res = self.client.get("jukeboxControl", args).await?;
-        Ok(serde_json::from_value(res)?)
+    /// Sends an action to the jukebox.
+    ///
+    /// The methods below cover each action; this is useful for passing
+    /// actions around, such as from a remote control.
+    pub async fn control(&self, action: JukeboxAction) -> Result<JukeboxResponse> {
+        let res = self.client.get("jukeboxControl", action.to_query()).await?;
+        JukeboxResponse::parse(&action, res)
+    }
+
+    async fn send_action(&self, action: JukeboxAction) -> Result<JukeboxStatus> {
+        Ok(self.control(action).await?.into_status())
     }
 
-    async fn send_action(&self, action: &str) -> Result<JukeboxStatus> {
-        self.send_action_with(action, None, &[]).await
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
     }
 
     /// Returns the current playlist of the jukebox, as well as its status. The
     /// status is also returned as it contains the position of the jukebox
     /// in its playlist.
     pub async fn playlist(&self) -> Result<JukeboxPlaylist> {
-        let res = self
-            .client
-            .get("jukeboxControl", Query::with("action", "get"))
-            .await?;
-        Ok(serde_json::from_value::<JukeboxPlaylist>(res)?)
+        match self.control(JukeboxAction::Get).await? {
+            JukeboxResponse::Playlist(playlist) => Ok(playlist),
+            JukeboxResponse::Status(status) => Ok(JukeboxPlaylist {
+                status,
+                songs: Vec::new(),
+            }),
+        }
     }
 
     /// Returns the status of the jukebox.
     pub async fn status(&self) -> Result<JukeboxStatus> {
-        self.send_action("status").await
+        self.send_action(JukeboxAction::Status).await
+    }
+
+    /// Repeatedly fetches the status of the jukebox, waiting `interval`
+    /// between each request.
+    ///
+    /// The first status is fetched immediately. If `until_stopped` is set,
+    /// the stream ends after yielding the first status where the jukebox is
+    /// no longer playing; otherwise it continues until dropped. The stream
+    /// also ends after yielding an error.
+    ///
+    /// The interval is timed with `tokio`, so the stream must be polled from
+    /// within a `tokio` runtime.
+    pub fn poll_status(
+        &self,
+        interval: Duration,
+        until_stopped: bool,
+    ) -> impl Stream<Item = Result<JukeboxStatus>> + '_ {
+        stream::unfold(Some(true), move |state| async move {
+            let first = state?;
+            if !first {
+                tokio::time::sleep(interval).await;
+            }
+            let status = self.status().await;
+            let next = match status {
+                Ok(ref s) if s.playing || !until_stopped => Some(false),
+                _ => None,
+            };
+            Some((status, next))
+        })
     }
 
     /// Tells the jukebox to start playing.
     pub async fn play(&self) -> Result<JukeboxStatus> {
-        self.send_action("start").await
+        self.send_action(JukeboxAction::Start).await
     }
 
     /// Tells the jukebox to pause playback.
     pub async fn stop(&self) -> Result<JukeboxStatus> {
-        self.send_action("stop").await
+        self.send_action(JukeboxAction::Stop).await
     }
 
     /// Moves the jukebox's currently playing song to the provided index
@@ -129,12 +241,12 @@ impl<'a> Jukebox<'a> {
     /// Using an index outside the range of the jukebox playlist will play the
     /// last song in the playlist.
     pub async fn skip_to(&self, n: usize) -> Result<JukeboxStatus> {
-        self.send_action_with("skip", n, &[]).await
+        self.send_action(JukeboxAction::Skip(n)).await
     }
 
     /// Adds the song to the jukebox's playlist.
     pub async fn add(&self, song: &Song) -> Result<JukeboxStatus> {
-        self.send_action_with("add", None, &[&song.id])
+        self.send_action(JukeboxAction::Add(vec![song.id.clone()]))
             .await
     }
 
@@ -145,16 +257,15 @@ impl<'a> Jukebox<'a> {
     /// The method will return an error if a song matching the provided ID
     /// cannot be found.
     pub async fn add_id(&self, id: &str) -> Result<JukeboxStatus> {
-        self.send_action_with("add", None, &[id]).await
+        self.send_action(JukeboxAction::Add(Jukebox::ids(&[id])))
+            .await
     }
 
     /// Adds all the songs to the jukebox's playlist.
     pub async fn add_all(&self, songs: &[Song]) -> Result<JukeboxStatus> {
-        self.send_action_with(
-            "add",
-            None,
-            &songs.iter().map(|s| s.id.as_ref()).collect::<Vec<_>>(),
-        )
+        self.send_action(JukeboxAction::Add(
+            songs.iter().map(|s| s.id.clone()).collect(),
+        ))
         .await
     }
 
@@ -165,31 +276,30 @@ impl<'a> Jukebox<'a> {
     /// The method will return an error if at least one ID cannot be matched to
     /// a song.
     pub async fn add_all_ids(&self, ids: &[&str]) -> Result<JukeboxStatus> {
-        self.send_action_with("add", None, ids).await
+        self.send_action(JukeboxAction::Add(Jukebox::ids(ids)))
+            .await
     }
 
     /// Clears the jukebox's playlist.
     pub async fn clear(&self) -> Result<JukeboxStatus> {
-        self.send_action("clear").await
+        self.send_action(JukeboxAction::Clear).await
     }
 
     /// Removes the song at the provided index from the playlist.
     pub async fn remove_id(&self, idx: usize) -> Result<JukeboxStatus> {
-        self.send_action_with("remove", idx, &[]).await
+        self.send_action(JukeboxAction::Remove(idx)).await
     }
 
     /// Shuffles the jukebox's playlist.
     pub async fn shuffle(&self) -> Result<JukeboxStatus> {
-        self.send_action("shuffle").await
+        self.send_action(JukeboxAction::Shuffle).await
     }
 
     /// Sets the jukebox's playback volume.
     ///
     /// Seting the volume above `1.0` will have no effect.
     pub async fn set_volume(&self, volume: f32) -> Result<JukeboxStatus> {
-        let args = Query::with("action", "setGain").arg("gain", volume).build();
-        let res = self.client.get("jukeboxControl", args).await?;
-        Ok(serde_json::from_value(res)?)
+        self.send_action(JukeboxAction::SetGain(volume)).await
     }
 }
 
@@ -264,4 +374,37 @@ mod tests {
         assert!(!parsed.status.playing);
         assert_eq!(parsed.status.volume, 0.75);
     }
+
+    #[test]
+    fn action_queries() {
+        let add = JukeboxAction::Add(vec!["27".into(), "28".into()]);
+        assert_eq!(add.to_query().to_string(), "action=add&id=27&id=28");
+        assert_eq!(
+            JukeboxAction::Skip(3).to_query().to_string(),
+            "action=skip&index=3"
+        );
+        assert_eq!(JukeboxAction::Get.to_query().to_string(), "action=get");
+    }
+
+    #[test]
+    fn response_by_action() {
+        let raw = serde_json::json!({
+            "currentIndex" : -1,
+            "playing" : false,
+            "gain" : 0.5,
+            "position" : 0
+        });
+
+        let get = JukeboxResponse::parse(&JukeboxAction::Get, raw.clone()).unwrap();
+        match get {
+            JukeboxResponse::Playlist(ref p) => assert!(p.songs.is_empty()),
+            _ => panic!("expected a playlist, got {get:?}"),
+        }
+
+        let stop = JukeboxResponse::parse(&JukeboxAction::Stop, raw).unwrap();
+        match stop {
+            JukeboxResponse::Status(ref s) => assert_eq!(s.index, -1),
+            _ => panic!("expected a status, got {stop:?}"),
+        }
+    }
 }
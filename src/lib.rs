@@ -112,31 +112,44 @@ extern crate serde_json;
 
 #[macro_use]
 mod macros;
+mod cache;
 mod client;
 mod error;
+mod metrics;
+mod xml;
 
 pub mod annotate;
+pub mod capabilities;
 pub mod collections;
 pub mod jukebox;
 pub mod media;
 pub mod query;
 pub mod response;
 pub mod search;
+pub mod units;
 pub mod user;
+pub mod util;
 pub mod version;
 
 #[cfg(test)]
 mod test_util;
 
-pub use self::client::Client;
+pub use self::capabilities::{Capabilities, ServerFlavor};
+pub use self::client::{BinaryResponse, Client, Credentials, SaltCharset, TokenInfo};
 pub use self::collections::Playlist;
 pub use self::collections::{Album, AlbumInfo, ListType};
-pub use self::collections::{Artist, ArtistInfo};
-pub use self::collections::{Genre, MusicFolder};
+pub use self::collections::{Artist, ArtistInfo, ImageSize};
+pub use self::collections::FolderScope;
+pub use self::collections::{Genre, GenreSort, Index, IndexArtist, Indexes, MusicFolder};
 pub use self::error::{ApiError, Error, Result, UrlError};
-pub use self::jukebox::{Jukebox, JukeboxPlaylist, JukeboxStatus};
+pub use self::jukebox::{Jukebox, JukeboxAction, JukeboxPlaylist, JukeboxResponse, JukeboxStatus};
 pub use self::media::{podcast, song, video};
-pub use self::media::{Hls, HlsPlaylist, Media, NowPlaying, RadioStation, Streamable};
+pub use self::metrics::Metrics;
+pub use self::media::{
+    CoverArtSize, Hls, HlsPlaylist, Media, MediaStream, NowPlaying, Progress, RadioStation,
+    StreamFormat, StreamParams, Streamable,
+};
 use self::song::{Lyrics, Song};
+pub use self::units::{Millis, Seconds};
 pub use self::user::{User, UserBuilder};
 pub use self::version::Version;
@@ -1,11 +1,20 @@
 //! Individual media APIs.
 
+use std::fmt;
 use std::ops::Index;
+use std::path::PathBuf;
+use std::pin::Pin;
 use std::result;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
 
+use bytes::Bytes;
+use futures_util::stream::{Stream, StreamExt};
 use serde::de::{Deserialize, Deserializer};
 
+use crate::metrics::Counters;
+use crate::query::Query;
 use crate::{Client, Error, Result};
 
 pub mod format;
@@ -85,6 +94,261 @@ pub trait Streamable {
     fn set_transcoding(&mut self, format: &str);
 }
 
+/// Options for streaming media from the server.
+///
+/// Any option left unset uses the server's default for the user.
+///
+/// # Examples
+///
+/// ```no_run
+/// use sunk::{Client, StreamParams};
+///
+/// # fn run() -> sunk::Result<()> {
+/// # let site = "http://demo.subsonic.org";
+/// # let user = "guest3";
+/// # let password = "guest";
+/// let client = Client::new(site, user, password)?;
+///
+/// let params = StreamParams::new().with_max_bit_rate(128).with_format("mp3");
+/// let url = client.stream_url_for("27", &params)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamParams {
+    /// The maximum bit rate to stream at, in Kbps. See
+    /// [`Streamable::set_max_bit_rate`] for supported values.
+    ///
+    /// [`Streamable::set_max_bit_rate`]: ./trait.Streamable.html#tymethod.set_max_bit_rate
+    pub max_bit_rate: Option<usize>,
+    /// The format to transcode to. See [`Streamable::set_transcoding`] for
+    /// supported values.
+    ///
+    /// [`Streamable::set_transcoding`]: ./trait.Streamable.html#tymethod.set_transcoding
+    pub format: Option<String>,
+    /// Whether the server should estimate the `Content-Length` of the
+    /// stream. Transcoded streams have no known length otherwise, which
+    /// prevents most players from seeking.
+    pub estimate_content_length: Option<bool>,
+}
+
+impl StreamParams {
+    /// Creates a set of options that use the server defaults.
+    pub fn new() -> StreamParams {
+        StreamParams::default()
+    }
+
+    /// Sets the maximum bit rate to stream at.
+    pub fn with_max_bit_rate(self, bit_rate: usize) -> StreamParams {
+        StreamParams {
+            max_bit_rate: Some(bit_rate),
+            ..self
+        }
+    }
+
+    /// Sets the format to transcode to.
+    pub fn with_format(self, format: &str) -> StreamParams {
+        StreamParams {
+            format: Some(format.to_string()),
+            ..self
+        }
+    }
+
+    /// Sets whether the server should estimate the length of the stream.
+    pub fn with_estimate_content_length(self, estimate: bool) -> StreamParams {
+        StreamParams {
+            estimate_content_length: Some(estimate),
+            ..self
+        }
+    }
+
+    /// Builds the query to stream the media with the given ID.
+    pub(crate) fn to_query(&self, id: &str) -> Query {
+        Query::with("id", id)
+            .arg("maxBitRate", self.max_bit_rate)
+            .arg("format", self.format.as_deref())
+            .arg("estimateContentLength", self.estimate_content_length)
+            .build()
+    }
+}
+
+/// A format that media can be streamed in.
+///
+/// Used to pick a format a player can handle; see [`Song::stream_best`].
+///
+/// [`Song::stream_best`]: ./song/struct.Song.html#method.stream_best
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFormat {
+    /// The file as stored on the server, without transcoding.
+    Raw,
+    /// MP3.
+    Mp3,
+    /// Opus, in an Ogg container.
+    Opus,
+    /// Vorbis, in an Ogg container.
+    Ogg,
+    /// AAC.
+    Aac,
+    /// FLAC.
+    Flac,
+}
+
+impl StreamFormat {
+    /// Returns the name of the format, as passed to the server.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StreamFormat::Raw => "raw",
+            StreamFormat::Mp3 => "mp3",
+            StreamFormat::Opus => "opus",
+            StreamFormat::Ogg => "ogg",
+            StreamFormat::Aac => "aac",
+            StreamFormat::Flac => "flac",
+        }
+    }
+
+    /// Returns whether a file with the given suffix or MIME type is already
+    /// in this format.
+    pub(crate) fn matches(self, suffix: &str, content_type: &str) -> bool {
+        let (suffixes, types): (&[&str], &[&str]) = match self {
+            StreamFormat::Raw => return false,
+            StreamFormat::Mp3 => (&["mp3"], &["audio/mpeg"]),
+            StreamFormat::Opus => (&["opus"], &["audio/opus"]),
+            StreamFormat::Ogg => (&["ogg", "oga"], &["audio/ogg", "audio/vorbis"]),
+            StreamFormat::Aac => (&["aac", "m4a"], &["audio/aac", "audio/mp4", "audio/x-m4a"]),
+            StreamFormat::Flac => (&["flac"], &["audio/flac", "audio/x-flac"]),
+        };
+        suffixes.iter().any(|s| s.eq_ignore_ascii_case(suffix))
+            || types.iter().any(|t| t.eq_ignore_ascii_case(content_type))
+    }
+}
+
+impl fmt::Display for StreamFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The body of a media response, read as it arrives rather than all at once.
+///
+/// Yields the body in chunks; combined with the [`content_length`], this
+/// allows reporting the progress of a download.
+///
+/// [`content_length`]: #method.content_length
+pub struct MediaStream {
+    content_length: Option<u64>,
+    inner: Pin<Box<dyn Stream<Item = Result<Bytes>> + Send>>,
+}
+
+impl MediaStream {
+    pub(crate) fn new(res: reqwest::Response, metrics: Arc<Counters>) -> MediaStream {
+        let content_length = res.content_length();
+        let inner = res
+            .bytes_stream()
+            .map(|chunk| chunk.map_err(Error::from))
+            .inspect(move |chunk| match chunk {
+                Ok(c) => metrics.downloaded(c.len()),
+                Err(e) => metrics.error(e),
+            });
+        MediaStream {
+            content_length,
+            inner: Box::pin(inner),
+        }
+    }
+
+    /// Wraps a body that has already been read.
+    pub(crate) fn from_bytes(data: Vec<u8>) -> MediaStream {
+        MediaStream {
+            content_length: Some(data.len() as u64),
+            inner: Box::pin(futures_util::stream::once(async { Ok(Bytes::from(data)) })),
+        }
+    }
+
+    /// Returns the total length of the body in bytes, if the server sent it.
+    ///
+    /// Transcoded streams will typically only have a length if the server was
+    /// asked to [estimate it].
+    ///
+    /// [estimate it]: ./struct.StreamParams.html#method.with_estimate_content_length
+    pub fn content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+}
+
+impl Stream for MediaStream {
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+impl fmt::Debug for MediaStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MediaStream")
+            .field("content_length", &self.content_length)
+            .finish()
+    }
+}
+
+/// The progress of a download of several files, such as
+/// [`Album::download_all_with`].
+///
+/// [`Album::download_all_with`]: ../struct.Album.html#method.download_all_with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    /// Bytes of the current file received so far.
+    pub bytes_done: u64,
+    /// The length of the current file, if the server sent it.
+    pub total: Option<u64>,
+    /// Where the current file is being written.
+    pub current_file: PathBuf,
+    /// How many files have finished, whether or not they succeeded.
+    pub files_done: usize,
+    /// How many files are being downloaded in all.
+    pub files_total: usize,
+}
+
+/// Sizes of cover art that a display can reasonably need, in pixels.
+const COVER_ART_SIZES: [usize; 4] = [150, 300, 600, 1200];
+
+/// A size to request cover art in.
+///
+/// Can be passed anywhere a cover art size is accepted, such as
+/// [`Media::cover_art`].
+///
+/// [`Media::cover_art`]: ./trait.Media.html#tymethod.cover_art
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverArtSize {
+    /// The image as stored on the server.
+    Original,
+    /// Exactly the given size, in pixels.
+    Exact(usize),
+    /// A size suitable for displaying the image at the given size.
+    ///
+    /// The size is rounded up to one of 150, 300, 600 or 1200 pixels, so that
+    /// displays of similar sizes share the same image, and any caches are
+    /// better used. Anything larger than 1200 pixels uses the original.
+    ForDisplay(usize),
+}
+
+impl CoverArtSize {
+    /// Returns the size to request from the server, or `None` for the
+    /// original image.
+    pub fn pixels(self) -> Option<usize> {
+        match self {
+            CoverArtSize::Original => None,
+            CoverArtSize::Exact(n) => Some(n),
+            CoverArtSize::ForDisplay(n) => COVER_ART_SIZES.iter().find(|&&s| s >= n).cloned(),
+        }
+    }
+}
+
+impl From<CoverArtSize> for Option<usize> {
+    fn from(size: CoverArtSize) -> Option<usize> {
+        size.pixels()
+    }
+}
+
 /// A trait deriving common methods for any form of media.
 #[async_trait::async_trait]
 pub trait Media {
@@ -127,26 +391,51 @@ pub trait Media {
     /// Aside from errors that the `Client` may cause, the method will error
     /// if the media does not have an associated cover art.
     fn cover_art_url<U: Into<Option<usize>>>(&self, client: &Client, size: U) -> Result<String>;
+
+    /// Returns the cover art of the media as a `data:` URI, which embeds the
+    /// image itself. See [`Client::cover_art_data_uri`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Aside from errors that the `Client` may cause, the method will error
+    /// if the media does not have an associated cover art.
+    ///
+    /// [`Client::cover_art_data_uri`]: ../struct.Client.html#method.cover_art_data_uri
+    async fn cover_art_data_uri<U: Into<Option<usize>> + Send>(
+        &self,
+        client: &Client,
+        size: U,
+    ) -> Result<String>
+    where
+        Self: Sync,
+    {
+        let cover = self.cover_id().ok_or(Error::Other("no cover art found"))?;
+        client.cover_art_data_uri(cover, size).await
+    }
 }
 
 /// Information about currently playing media.
 ///
 /// Due to the "now playing" information possibly containing both audio and
-/// video, compromises are made. `NowPlaying` only stores the ID, title, and
-/// content type of the media. This is most of the information afforded through
-/// the web interface. For more detailed information, `song_info()` or
-/// `video_info()` gives the full `Song` or `Video` struct, though requires
-/// another web request.
-#[derive(Debug)]
+/// video, compromises are made. `NowPlaying` describes the media using the
+/// fields of a [`Song`], which videos mostly share. For a video,
+/// `video_info()` gives the full `Video` struct, though requires another web
+/// request.
+///
+/// [`Song`]: ./song/struct.Song.html
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct NowPlaying {
     /// The user streaming the current media.
+    #[serde(rename = "username")]
     pub user: String,
     /// How long ago the user sent an update to the server.
     pub minutes_ago: usize,
     /// The ID of the player.
     pub player_id: usize,
-    id: String,
-    is_video: bool,
+    /// The media being played.
+    #[serde(flatten)]
+    pub song: Song,
 }
 
 impl NowPlaying {
@@ -159,10 +448,10 @@ impl NowPlaying {
     ///
     /// [`Client`]: ../struct.Client.html
     pub async fn song_info(&self, client: &Client) -> Result<Song> {
-        if self.is_video {
+        if self.is_video() {
             Err(Error::Other("Now Playing info is not a song"))
         } else {
-            Song::get(client, &self.id).await
+            Song::get(client, &self.song.id).await
         }
     }
 
@@ -175,21 +464,21 @@ impl NowPlaying {
     ///
     /// [`Client`]: ../struct.Client.html
     pub async fn video_info(&self, client: &Client) -> Result<Video> {
-        if !self.is_video {
+        if !self.is_video() {
             Err(Error::Other("Now Playing info is not a video"))
         } else {
-            Video::get(client, &self.id).await
+            Video::get(client, &self.song.id).await
         }
     }
 
     /// Returns `true` if the currently playing media is a song.
     pub fn is_song(&self) -> bool {
-        !self.is_video
+        !self.is_video()
     }
 
     /// Returns `true` if the currently playing media is a video.
     pub fn is_video(&self) -> bool {
-        self.is_video
+        self.song.is_video
     }
 }
 
@@ -246,7 +535,7 @@ impl FromStr for HlsPlaylist {
     fn from_str(s: &str) -> result::Result<Self, Self::Err> {
         fn chew<'a>(s: &'a str, head: &str) -> result::Result<&'a str, Error> {
             if s.starts_with(head) {
-                return Ok(s.trim_start_matches(head));
+                Ok(s.trim_start_matches(head))
             } else {
                 Err(Error::Other("missing required field"))
             }
@@ -311,19 +600,8 @@ impl<'de> Deserialize<'de> for NowPlaying {
             username: String,
             minutes_ago: usize,
             player_id: usize,
-            id: String,
-            // is_dir: bool,
-            // title: String,
-            // size: usize,
-            // content_type: String,
-            // suffix: String,
-            // transcoded_content_type: Option<String>,
-            // transcoded_suffix: Option<String>,
-            // path: String,
-            is_video: bool,
-            // created: String,
-            // #[serde(rename = "type")]
-            // media_type: String,
+            #[serde(flatten)]
+            song: Song,
         }
 
         let raw = _NowPlaying::deserialize(de)?;
@@ -332,8 +610,7 @@ impl<'de> Deserialize<'de> for NowPlaying {
             user: raw.username,
             minutes_ago: raw.minutes_ago,
             player_id: raw.player_id,
-            id: raw.id.parse().unwrap(),
-            is_video: raw.is_video,
+            song: raw.song,
         })
     }
 }
@@ -342,6 +619,97 @@ impl<'de> Deserialize<'de> for NowPlaying {
 mod tests {
     use super::*;
 
+    #[test]
+    fn cover_art_display_sizes() {
+        assert_eq!(CoverArtSize::ForDisplay(48).pixels(), Some(150));
+        assert_eq!(CoverArtSize::ForDisplay(300).pixels(), Some(300));
+        assert_eq!(CoverArtSize::ForDisplay(301).pixels(), Some(600));
+        assert_eq!(CoverArtSize::ForDisplay(2000).pixels(), None);
+        assert_eq!(Option::<usize>::from(CoverArtSize::Exact(512)), Some(512));
+    }
+
+    #[test]
+    fn stream_params_query() {
+        let params = StreamParams::new()
+            .with_max_bit_rate(96)
+            .with_format("opus")
+            .with_estimate_content_length(true);
+        assert_eq!(
+            params.to_query("27").to_string(),
+            "id=27&maxBitRate=96&format=opus&estimateContentLength=true"
+        );
+
+        let params = params.with_estimate_content_length(false);
+        assert!(params
+            .to_query("27")
+            .to_string()
+            .ends_with("estimateContentLength=false"));
+    }
+
+    #[test]
+    fn parse_now_playing() {
+        let parsed = serde_json::from_str::<NowPlaying>(
+            r#"{
+            "username" : "guest3",
+            "minutesAgo" : 2,
+            "playerId" : 19,
+            "id" : "27",
+            "parent" : "25",
+            "isDir" : false,
+            "title" : "Bellevue Avenue",
+            "album" : "Bellevue",
+            "artist" : "Misteur Valaire",
+            "track" : 1,
+            "coverArt" : "25",
+            "size" : 5400185,
+            "contentType" : "audio/mpeg",
+            "suffix" : "mp3",
+            "duration" : 198,
+            "path" : "Misteur Valaire/Bellevue/01 - Misteur Valaire - Bellevue Avenue.mp3",
+            "isVideo" : false,
+            "albumId" : "1",
+            "artistId" : "1",
+            "type" : "music"
+        }"#,
+        )
+        .unwrap();
+
+        assert_eq!(parsed.user, "guest3");
+        assert_eq!(parsed.minutes_ago, 2);
+        assert!(parsed.is_song());
+        assert_eq!(parsed.song.id, "27");
+        assert_eq!(parsed.song.album.as_deref(), Some("Bellevue"));
+        assert_eq!(parsed.song.duration, Some(198));
+        assert_eq!(parsed.song.cover_id.as_deref(), Some("25"));
+
+        let value = serde_json::to_value(&parsed).unwrap();
+        assert_eq!(value["title"], "Bellevue Avenue");
+        assert_eq!(value["minutesAgo"], 2);
+    }
+
+    #[test]
+    fn parse_now_playing_video() {
+        let parsed = serde_json::from_value::<NowPlaying>(serde_json::json!({
+            "username": "guest3",
+            "minutesAgo": 0,
+            "playerId": 19,
+            "id": "460",
+            "title": "Big Buck Bunny",
+            "size": 52464391,
+            "contentType": "video/mp4",
+            "suffix": "mp4",
+            "isVideo": true,
+            "type": "video"
+        }))
+        .unwrap();
+
+        assert!(parsed.is_video());
+        assert!(parsed.song.is_video);
+        let json = serde_json::to_string(&parsed).unwrap();
+        assert_eq!(json.matches("\"isVideo\"").count(), 1);
+        assert!(json.contains("\"isVideo\":true"));
+    }
+
     #[test]
     fn parse_hls() {
         let hls = hls();
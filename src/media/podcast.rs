@@ -8,9 +8,11 @@ use crate::query::Query;
 use crate::{Client, Result};
 
 #[allow(missing_docs)]
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 #[readonly::make]
 pub struct Podcast {
+    #[serde(serialize_with = "crate::util::serialize_display")]
     pub id: usize,
     pub url: String,
     pub title: String,
@@ -18,15 +20,21 @@ pub struct Podcast {
     pub cover_art: String,
     pub image_url: String,
     pub status: String,
+    #[serde(rename = "episode")]
     pub episodes: Vec<Episode>,
+    #[serde(rename = "errorMessage")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
 }
 
 #[allow(missing_docs)]
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 #[readonly::make]
 pub struct Episode {
+    #[serde(serialize_with = "crate::util::serialize_display")]
     pub id: usize,
+    #[serde(serialize_with = "crate::util::serialize_display")]
     pub parent: usize,
     pub is_dir: bool,
     pub title: String,
@@ -38,10 +46,12 @@ pub struct Episode {
     pub content_type: String,
     pub suffix: String,
     pub duration: usize,
+    #[serde(rename = "bitRate")]
     pub bitrate: usize,
     pub is_video: bool,
     pub created: String,
     pub artist_id: String,
+    #[serde(rename = "type")]
     pub media_type: String,
     pub stream_id: String,
     pub channel_id: String,
@@ -86,7 +96,10 @@ impl Episode {
         U: Into<Option<usize>>,
     {
         let episode = client
-            .get("getNewestPodcasts", Query::with("count", count.into()))
+            .get(
+                "getNewestPodcasts",
+                Query::with("count", client.page_size(count)),
+            )
             .await?;
         Ok(get_list_as!(episode, Episode))
     }
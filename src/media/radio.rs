@@ -8,12 +8,15 @@ use crate::query::Query;
 use crate::{Client, Result};
 
 #[allow(missing_docs)]
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 #[readonly::make]
 pub struct RadioStation {
+    #[serde(serialize_with = "crate::util::serialize_display")]
     pub id: usize,
     pub name: String,
     pub stream_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub homepage_url: Option<String>,
 }
 
@@ -28,6 +31,7 @@ impl<'de> Deserialize<'de> for RadioStation {
             id: String,
             name: String,
             stream_url: String,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
             homepage_url: Option<String>,
         }
         let raw = _Station::deserialize(de)?;
@@ -78,6 +82,20 @@ impl RadioStation {
         Ok(())
     }
 
+    /// Checks whether the station's stream is reachable, by requesting it
+    /// directly rather than through the Subsonic server.
+    ///
+    /// Only the start of the response is waited for; the stream itself isn't
+    /// read. Stations that can't be connected to, answer with an HTTP error,
+    /// or don't answer within five seconds are unreachable.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the station's stream URL isn't a valid URL.
+    pub async fn probe(&self, client: &Client) -> Result<bool> {
+        client.probe_external(&self.stream_url).await
+    }
+
     pub async fn delete(&self, client: &Client) -> Result<()> {
         client
             .get("deleteInternetRadioStation", Query::with("id", self.id))
@@ -85,3 +103,29 @@ impl RadioStation {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    fn station(url: &str) -> RadioStation {
+        serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "name": "CBC Radio 2",
+            "streamUrl": url,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn probe_stations() {
+        let cli = test_util::fixture_site().unwrap();
+        let live = cli.build_url("ping", Query::none()).unwrap();
+        let dead = cli.build_url("missing", Query::none()).unwrap();
+
+        assert!(tokio_test::block_on(station(&live).probe(&cli)).unwrap());
+        assert!(!tokio_test::block_on(station(&dead).probe(&cli)).unwrap());
+        assert!(tokio_test::block_on(station("not a url").probe(&cli)).is_err());
+    }
+}
@@ -1,17 +1,59 @@
 //! Song APIs.
 
+use std::collections::HashSet;
 use std::fmt;
+use std::io::{Seek, SeekFrom, Write};
 use std::ops::Range;
 
+use futures_util::StreamExt;
+
 use serde::de::{Deserialize, Deserializer};
 use serde_json;
 
+use crate::annotate::Annotatable;
 use crate::query::Query;
 use crate::search::SearchPage;
-use crate::{Client, Error, HlsPlaylist, Media, Result, Streamable};
+use crate::{
+    Album, Client, Error, HlsPlaylist, Media, MediaStream, Result, StreamFormat, StreamParams,
+    Streamable,
+};
+
+/// The kind of media a [`Song`] entry holds.
+///
+/// [`Song`]: ./struct.Song.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKind {
+    /// Music, or any other audio that isn't a podcast.
+    Audio,
+    /// A video listed among songs.
+    Video,
+    /// A podcast episode.
+    Podcast,
+}
+
+/// How to fetch a song's audio with [`Song::fetch_audio`].
+///
+/// [`Song::fetch_audio`]: ./struct.Song.html#method.fetch_audio
+#[derive(Debug, Clone, PartialEq)]
+pub enum AudioPolicy {
+    /// Download the file as stored on the server.
+    Original,
+    /// Stream the song, transcoded as described.
+    Transcode(StreamParams),
+    /// Download the file if its bit rate is below the given one, in Kbps;
+    /// otherwise, stream it transcoded to that bit rate. Songs with an
+    /// unknown bit rate are transcoded.
+    PreferOriginalUnder(usize),
+}
+
+/// The most songs `getRandomSongs` returns at once.
+const RANDOM_MAX_SIZE: usize = 500;
+/// How many times to ask for random songs before settling for fewer.
+const RANDOM_ATTEMPTS: usize = 10;
 
 /// A work of music contained on a Subsonic server.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 #[readonly::make]
 pub struct Song {
     /// Unique identifier for the song.
@@ -20,20 +62,40 @@ pub struct Song {
     /// the file name.
     pub title: String,
     /// Album the song belongs to. Reads from the song's ID3 tags.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub album: Option<String>,
     /// The ID of the released album.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub album_id: Option<String>,
     /// Credited artist for the song. Reads from the song's ID3 tags.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub artist: Option<String>,
     /// The ID of the releasing artist.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub artist_id: Option<String>,
+    /// Every artist credited on the song.
+    ///
+    /// Only servers implementing the OpenSubsonic extensions list multiple
+    /// artists; otherwise, this holds the single [`artist`](#structfield.artist)
+    /// if the song has one.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub artists: Vec<ArtistRef>,
+    /// The artist credit as it should be displayed, such as
+    /// "Artist feat. Other Artist". Falls back to the single credited artist.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_artist: Option<String>,
     /// Position of the song in the album.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub track: Option<u64>,
     /// Year the song was released.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub year: Option<u64>,
     /// Genre of the song.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub genre: Option<String>,
     /// ID of the song's cover art. Defaults to the parent album's cover.
+    #[serde(rename = "coverArt")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub cover_id: Option<String>,
     /// File size of the song, in bytes.
     pub size: u64,
@@ -42,21 +104,97 @@ pub struct Song {
     /// The file extension of the song.
     pub suffix: String,
     /// The MIME type that the song will be transcoded to.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub transcoded_content_type: Option<String>,
     /// The file extension that the song will be transcoded to.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub transcoded_suffix: Option<String>,
     /// Duration of the song, in seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub duration: Option<u64>,
-    /// The absolute path of the song in the server database.
-    pub path: String,
-    /// Will always be "song".
+    /// Bit rate of the song as stored on the server, in Kbps.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bit_rate: Option<u64>,
+    /// How many times the song has been played.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub play_count: Option<u64>,
+    /// An ISO8601 timestamp of when the song was last played. Only sent by
+    /// OpenSubsonic servers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub played: Option<String>,
+    /// An ISO8601 timestamp of when the user starred the song, or `None` if
+    /// it isn't starred.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub starred: Option<String>,
+    /// Where the user's bookmark in the song is, in milliseconds. Only sent
+    /// by OpenSubsonic servers, and only for songs with a bookmark.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bookmark_position: Option<u64>,
+    /// The path of the song, relative to the music folder it is stored in.
+    /// Some servers don't expose paths.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// The kind of media, such as "music" or "podcast". See [`media_kind`].
+    ///
+    /// [`media_kind`]: #method.media_kind
+    #[serde(rename = "type")]
     pub media_type: String,
+    /// Whether the entry is actually a video. Some servers list videos
+    /// alongside songs.
+    pub is_video: bool,
+    /// Loudness data for volume normalisation. Only sent by OpenSubsonic
+    /// servers, and only for songs that have been analysed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replay_gain: Option<ReplayGain>,
+    /// The [MusicBrainz](https://musicbrainz.org/) ID of the recording, if it is
+    /// tagged with one.
+    #[serde(rename = "musicBrainzId")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub musicbrainz_id: Option<String>,
     /// Bit rate the song will be downsampled to.
+    #[serde(skip)]
     pub stream_br: Option<usize>,
     /// Format the song will be transcoded to.
+    #[serde(skip)]
     pub stream_tc: Option<String>,
 }
 
+/// The ReplayGain data of a song, used to play songs at an even volume.
+///
+/// Gains are in decibels and peaks are linear amplitudes, where `1.0` is full
+/// scale. Servers leave out whatever they don't know.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayGain {
+    /// The gain to apply to play the song at the reference loudness.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track_gain: Option<f64>,
+    /// The gain to apply to play the song's album at the reference loudness.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub album_gain: Option<f64>,
+    /// The song's peak amplitude.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track_peak: Option<f64>,
+    /// The peak amplitude of the song's album.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub album_peak: Option<f64>,
+    /// A gain the other gains are relative to, if the server applies one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_gain: Option<f64>,
+    /// The gain the server suggests for songs without any other gain.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_gain: Option<f64>,
+}
+
+/// A reference to an artist credited on a song.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct ArtistRef {
+    /// The ID of the artist.
+    pub id: String,
+    /// The name of the artist.
+    pub name: String,
+}
+
 impl Song {
     /// Returns a single song from the Subsonic server.
     ///
@@ -65,7 +203,7 @@ impl Song {
     /// Aside from other errors the `Client` may cause, the server will return
     /// an error if there is no song matching the provided ID.
     pub async fn get(client: &Client, id: &str) -> Result<Song> {
-        let res = client.get("getSong", Query::with("id", id)).await?;
+        let res = client.get_entity("getSong", id).await?;
         Ok(serde_json::from_value(res)?)
     }
 
@@ -79,7 +217,7 @@ impl Song {
         U: Into<Option<usize>>,
     {
         let args = Query::with("id", self.id.as_ref())
-            .arg("count", count.into())
+            .arg("count", client.page_size(count))
             .build();
 
         let song = client.get("getSimilarSongs2", args).await?;
@@ -89,35 +227,64 @@ impl Song {
     /// Returns a number of random songs. Optionally accepts a maximum number
     /// of results to return.
     ///
+    /// Random songs can't be paged through; each call returns a fresh random
+    /// set, which may overlap with earlier ones. Use [`random_unique`] to
+    /// collect more distinct songs than one call returns.
+    ///
     /// Some parts of the query can be modified. Use [`random_with`] to be able
     /// to set these optional fields.
     ///
     /// [`random_with`]: #method.random_with
+    /// [`random_unique`]: #method.random_unique
     pub async fn random<U>(client: &Client, size: U) -> Result<Vec<Song>>
     where
         U: Into<Option<usize>>,
     {
-        let arg = Query::with("size", size.into().unwrap_or(10));
+        let arg = Query::with("size", client.page_size(size).unwrap_or(10));
         let song = client.get("getRandomSongs", arg).await?;
         Ok(get_list_as!(song, Song))
     }
 
+    /// Returns `total` distinct random songs, requesting random sets until
+    /// enough have been collected.
+    ///
+    /// Libraries with fewer songs than `total` can't fill the list, so this
+    /// gives up after a few requests and returns the songs found so far.
+    pub async fn random_unique(client: &Client, total: usize) -> Result<Vec<Song>> {
+        let mut seen = HashSet::new();
+        let mut songs = Vec::with_capacity(total);
+
+        for _ in 0..RANDOM_ATTEMPTS {
+            if songs.len() >= total {
+                break;
+            }
+            let size = (total - songs.len()).min(RANDOM_MAX_SIZE);
+            let batch = Song::random(client, size).await?;
+            add_unique(&mut songs, &mut seen, batch, total);
+        }
+
+        Ok(songs)
+    }
+
     /// Creates a new builder to request a set of random songs.
     ///
     /// See the [struct level documentation] for more information on how to use
     /// the builder.
     ///
     /// [struct level documentation]: ./struct.RandomSongs.html
-    pub async fn random_with(client: &Client) -> RandomSongs {
-        RandomSongs::new(client, 10)
+    pub async fn random_with(client: &Client) -> RandomSongs<'_> {
+        RandomSongs::new(client, client.page_size(None).unwrap_or(10))
     }
 
     /// Lists all the songs in a provided genre. Supports paging through the
     /// result.
     ///
+    /// The genre is matched ignoring case, using the client's [genre map].
+    ///
     /// See the [struct level documentation] about paging for more.
     ///
     /// [struct level documentation]: ../search/struct.SearchPage.html
+    /// [genre map]: ../struct.Client.html#method.genre_map
     pub async fn list_in_genre<U>(
         client: &Client,
         genre: &str,
@@ -127,8 +294,9 @@ impl Song {
     where
         U: Into<Option<u64>>,
     {
+        let genre = client.resolve_genre(genre).await;
         let args = Query::with("genre", genre)
-            .arg("count", page.count)
+            .arg("count", client.page_size(page.count))
             .arg("offset", page.offset)
             .arg("musicFolderId", folder_id.into())
             .build();
@@ -137,6 +305,290 @@ impl Song {
         Ok(get_list_as!(song, Song))
     }
 
+    /// Returns the kind of media the entry holds, to tell videos and podcasts
+    /// apart from songs before streaming them.
+    pub fn media_kind(&self) -> MediaKind {
+        if self.is_video || self.media_type == "video" {
+            MediaKind::Video
+        } else if self.media_type == "podcast" {
+            MediaKind::Podcast
+        } else {
+            MediaKind::Audio
+        }
+    }
+
+    /// Returns a file name to save the song as, such as
+    /// `"01 - Misteur Valaire - Bellevue Avenue.mp3"`.
+    ///
+    /// The track number and artist are left out if the song doesn't have
+    /// them. Characters that aren't allowed in file names on common
+    /// filesystems are replaced with underscores.
+    pub fn suggested_filename(&self) -> String {
+        let mut parts = Vec::with_capacity(3);
+        if let Some(track) = self.track {
+            parts.push(format!("{track:02}"));
+        }
+        if let Some(ref artist) = self.artist {
+            parts.push(artist.clone());
+        }
+        parts.push(self.title.clone());
+
+        let stem = crate::util::sanitize_file_stem(&parts.join(" - "));
+        match self.extension() {
+            Some(ext) => format!("{stem}.{}", crate::util::sanitize_file_stem(ext)),
+            None => stem,
+        }
+    }
+
+    /// Returns the file extension of the song, falling back to one matching
+    /// its content type.
+    fn extension(&self) -> Option<&str> {
+        if !self.suffix.is_empty() {
+            return Some(&self.suffix);
+        }
+        match self.content_type.as_ref() {
+            "audio/mpeg" => Some("mp3"),
+            "audio/flac" | "audio/x-flac" => Some("flac"),
+            "audio/ogg" => Some("ogg"),
+            "audio/opus" => Some("opus"),
+            "audio/mp4" | "audio/x-m4a" => Some("m4a"),
+            "audio/aac" => Some("aac"),
+            "audio/wav" | "audio/x-wav" => Some("wav"),
+            _ => None,
+        }
+    }
+
+    /// Returns the streaming options set on the song through [`Streamable`].
+    ///
+    /// [`Streamable`]: ../trait.Streamable.html
+    pub fn stream_params(&self) -> StreamParams {
+        StreamParams {
+            max_bit_rate: self.stream_br,
+            format: self.stream_tc.clone(),
+            ..StreamParams::default()
+        }
+    }
+
+    /// Predicts the file extension of the stream the server will send for
+    /// the given options, such as to pick a MIME type before it arrives.
+    ///
+    /// This is a guess, as servers choose how to transcode from their own
+    /// settings:
+    ///
+    /// - A format in `params` is used as is, except `"raw"`, which keeps the
+    ///   song's own suffix.
+    /// - Without a format, a song already under the maximum bit rate (or
+    ///   without a maximum) is assumed to stream as the server usually
+    ///   would: as its [`transcoded_suffix`] if it has one, otherwise as
+    ///   stored.
+    /// - Otherwise the song has to be downsampled, assumed to be to its
+    ///   [`transcoded_suffix`], or MP3, the most common server default.
+    ///
+    /// [`transcoded_suffix`]: #structfield.transcoded_suffix
+    pub fn predicted_stream_suffix<'a>(&'a self, params: &'a StreamParams) -> &'a str {
+        match params.format.as_deref() {
+            Some("raw") => return &self.suffix,
+            Some(format) => return format,
+            None => {}
+        }
+
+        let under_max = match (params.max_bit_rate, self.bit_rate) {
+            (None, _) => true,
+            (Some(max), Some(rate)) => rate <= max as u64,
+            (Some(_), None) => false,
+        };
+        match self.transcoded_suffix.as_deref() {
+            Some(transcoded) => transcoded,
+            None if under_max => &self.suffix,
+            None => "mp3",
+        }
+    }
+
+    /// Streams the song with the given options, yielding the body in chunks
+    /// as it is received.
+    ///
+    /// Unlike [`Streamable::stream`], this doesn't wait for the whole song to
+    /// arrive, and exposes the length of the body if the server sent one.
+    ///
+    /// [`Streamable::stream`]: ../trait.Streamable.html#tymethod.stream
+    pub async fn stream_chunks(
+        &self,
+        client: &Client,
+        params: &StreamParams,
+    ) -> Result<MediaStream> {
+        client.get_stream("stream", params.to_query(&self.id)).await
+    }
+
+    /// Streams the song with the given options, calling `f` with each chunk
+    /// as it is received. Returns the number of bytes received.
+    ///
+    /// This suits audio backends that are fed rather than read from. The
+    /// next chunk isn't read until `f` returns, so blocking in `f` holds the
+    /// stream back, and an error from `f` stops the stream and is returned.
+    pub async fn stream_with<F>(&self, client: &Client, params: &StreamParams, f: F) -> Result<u64>
+    where
+        F: FnMut(&[u8]) -> Result<()>,
+    {
+        let stream = self.stream_chunks(client, params).await?;
+        for_each_chunk(stream, f).await
+    }
+
+    /// Fetches the song's audio into `out`, downloading or streaming it as
+    /// `policy` decides. Returns the number of bytes written.
+    pub async fn fetch_audio<W>(
+        &self,
+        client: &Client,
+        policy: &AudioPolicy,
+        out: &mut W,
+    ) -> Result<u64>
+    where
+        W: Write,
+    {
+        let (endpoint, args) = self.audio_request(policy);
+        let stream = client.get_stream(endpoint, args).await?;
+        write_stream(stream, out).await
+    }
+
+    /// Streams the song with the given options into `out`, as it is
+    /// received. Returns the number of bytes written.
+    ///
+    /// After each chunk, `on_progress` is called with the number of bytes
+    /// written so far and the length of the stream, if the server sent it.
+    pub async fn stream_to<W, F>(
+        &self,
+        client: &Client,
+        params: &StreamParams,
+        out: &mut W,
+        on_progress: F,
+    ) -> Result<u64>
+    where
+        W: Write,
+        F: FnMut(u64, Option<u64>),
+    {
+        let stream = self.stream_chunks(client, params).await?;
+        write_stream_with(stream, out, on_progress).await
+    }
+
+    /// Downloads the song's original file into `out`, as it is received.
+    /// Returns the number of bytes written.
+    ///
+    /// `on_progress` is called the same way as for [`stream_to`].
+    ///
+    /// [`stream_to`]: #method.stream_to
+    pub async fn download_to<W, F>(
+        &self,
+        client: &Client,
+        out: &mut W,
+        on_progress: F,
+    ) -> Result<u64>
+    where
+        W: Write,
+        F: FnMut(u64, Option<u64>),
+    {
+        let stream = client
+            .get_stream("download", Query::with("id", self.id.as_ref()))
+            .await?;
+        write_stream_with(stream, out, on_progress).await
+    }
+
+    /// Streams the song into `out` in the first of the `accepted` formats
+    /// that suits it, returning the format used.
+    ///
+    /// If the song is already stored in an accepted format, or
+    /// [`StreamFormat::Raw`] is accepted, it is sent as is to avoid
+    /// transcoding. Otherwise, the server is asked to transcode to the first
+    /// accepted format. Any maximum bit rate set on the song still applies.
+    ///
+    /// [`StreamFormat::Raw`]: ../enum.StreamFormat.html#variant.Raw
+    ///
+    /// # Errors
+    ///
+    /// Aside from other errors the `Client` may cause, the method will error
+    /// if no formats are accepted.
+    pub async fn stream_best<W>(
+        &self,
+        client: &Client,
+        accepted: &[StreamFormat],
+        out: &mut W,
+    ) -> Result<StreamFormat>
+    where
+        W: Write,
+    {
+        let format = self
+            .best_format(accepted)
+            .ok_or(Error::Other("no stream formats accepted"))?;
+        let params = self.stream_params().with_format(format.as_str());
+        let stream = client
+            .get_stream("stream", params.to_query(&self.id))
+            .await?;
+        write_stream(stream, out).await?;
+        Ok(format)
+    }
+
+    /// Picks the format to stream the song in out of the accepted ones.
+    fn best_format(&self, accepted: &[StreamFormat]) -> Option<StreamFormat> {
+        let raw = accepted
+            .iter()
+            .any(|f| *f == StreamFormat::Raw || f.matches(&self.suffix, &self.content_type));
+        if raw {
+            Some(StreamFormat::Raw)
+        } else {
+            accepted.first().cloned()
+        }
+    }
+
+    /// Returns the endpoint and arguments to fetch the song's audio with.
+    fn audio_request(&self, policy: &AudioPolicy) -> (&'static str, Query) {
+        let download = || ("download", Query::with("id", self.id.as_ref()));
+        match *policy {
+            AudioPolicy::Original => download(),
+            AudioPolicy::Transcode(ref params) => ("stream", params.to_query(&self.id)),
+            AudioPolicy::PreferOriginalUnder(limit) => match self.bit_rate {
+                Some(bit_rate) if bit_rate < limit as u64 => download(),
+                _ => {
+                    let params = StreamParams::new().with_max_bit_rate(limit);
+                    ("stream", params.to_query(&self.id))
+                }
+            },
+        }
+    }
+
+    /// Streams the song into `out`, resuming an earlier, interrupted stream
+    /// that ended at `from_byte`.
+    ///
+    /// The server is asked for only the rest of the song, which is written
+    /// from `from_byte` onwards. If the server doesn't support resuming and
+    /// sends the whole song instead, it is written from the start of `out`.
+    /// Either way, returns the position the song ends at in `out`; when
+    /// writing to a file, truncate it to this length afterwards, in case the
+    /// restarted stream was shorter than what was already there.
+    ///
+    /// Resuming is only reliable without transcoding, as the server can't
+    /// otherwise know where a byte offset falls.
+    pub async fn stream_to_resumable<W>(
+        &self,
+        client: &Client,
+        out: &mut W,
+        from_byte: u64,
+    ) -> Result<u64>
+    where
+        W: Write + Seek,
+    {
+        let (partial, mut stream) = client
+            .get_stream_from("stream", self.stream_params().to_query(&self.id), from_byte)
+            .await?;
+
+        let mut pos = if partial { from_byte } else { 0 };
+        out.seek(SeekFrom::Start(pos))?;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            out.write_all(&chunk)?;
+            pos += chunk.len() as u64;
+        }
+        out.flush()?;
+        Ok(pos)
+    }
+
     /// Creates an HLS (HTTP Live Streaming) playlist used for streaming video
     /// or audio. HLS is a streaming protocol implemented by Apple and works by
     /// breaking the overall stream into a sequence of small HTTP-based file
@@ -159,20 +611,63 @@ impl Song {
         let raw = client.get_raw("hls", args).await?;
         raw.parse::<HlsPlaylist>()
     }
+
+    /// Stars the song if it isn't starred, or unstars it if it is, returning
+    /// whether it is now starred.
+    ///
+    /// Whether the song is starred is taken from [`starred`], so a song
+    /// fetched before it was starred elsewhere will be starred again rather
+    /// than unstarred.
+    ///
+    /// [`starred`]: #structfield.starred
+    pub async fn toggle_star(&self, client: &Client) -> Result<bool> {
+        if self.starred.is_none() {
+            self.star(client).await?;
+            Ok(true)
+        } else {
+            self.unstar(client).await?;
+            Ok(false)
+        }
+    }
+
+    /// Returns a URL for the song's cover art, or its album's if the song
+    /// has none of its own.
+    ///
+    /// Loose files often carry no art, while the album they belong to does.
+    /// Looking up the album costs a request, which is only made when the
+    /// song has no cover art.
+    ///
+    /// # Errors
+    ///
+    /// Errors if neither the song nor its album has cover art.
+    pub async fn best_cover_art_url<U>(&self, client: &Client, size: U) -> Result<String>
+    where
+        U: Into<Option<usize>>,
+    {
+        if self.has_cover_art() {
+            return self.cover_art_url(client, size);
+        }
+
+        let album_id = self
+            .album_id
+            .as_deref()
+            .ok_or(Error::Other("no cover art found"))?;
+        Album::get(client, album_id)
+            .await?
+            .cover_art_url(client, size)
+    }
 }
 
 #[async_trait::async_trait]
 impl Streamable for Song {
     async fn stream(&self, client: &Client) -> Result<Vec<u8>> {
-        let mut q = Query::with("id", self.id.as_ref());
-        q.arg("maxBitRate", self.stream_br);
-        client.get_bytes("stream", q).await
+        client
+            .get_bytes("stream", self.stream_params().to_query(&self.id))
+            .await
     }
 
     fn stream_url(&self, client: &Client) -> Result<String> {
-        let mut q = Query::with("id", self.id.as_ref());
-        q.arg("maxBitRate", self.stream_br);
-        client.build_url("stream", q)
+        client.stream_url_for(&self.id, &self.stream_params())
     }
 
     async fn download(&self, client: &Client) -> Result<Vec<u8>> {
@@ -182,7 +677,7 @@ impl Streamable for Song {
     }
 
     fn download_url(&self, client: &Client) -> Result<String> {
-        client.build_url("download", Query::with("id", self.id.as_ref()))
+        client.download_url_for(&self.id)
     }
 
     fn encoding(&self) -> &str {
@@ -253,6 +748,58 @@ impl fmt::Display for Song {
     }
 }
 
+/// Writes a whole stream into `out`, returning the number of bytes written.
+async fn write_stream<W: Write>(stream: MediaStream, out: &mut W) -> Result<u64> {
+    write_stream_with(stream, out, |_, _| {}).await
+}
+
+/// Writes a whole stream into `out`, calling `on_progress` with the bytes
+/// written so far and the length of the stream after each chunk.
+async fn write_stream_with<W, F>(
+    stream: MediaStream,
+    out: &mut W,
+    mut on_progress: F,
+) -> Result<u64>
+where
+    W: Write,
+    F: FnMut(u64, Option<u64>),
+{
+    let total = stream.content_length();
+    let mut done = 0;
+    for_each_chunk(stream, |chunk| {
+        out.write_all(chunk)?;
+        done += chunk.len() as u64;
+        on_progress(done, total);
+        Ok(())
+    })
+    .await?;
+    out.flush()?;
+    Ok(done)
+}
+
+/// Calls `f` with each chunk of a stream, returning the number of bytes read.
+async fn for_each_chunk<F>(mut stream: MediaStream, mut f: F) -> Result<u64>
+where
+    F: FnMut(&[u8]) -> Result<()>,
+{
+    let mut read = 0;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        f(&chunk)?;
+        read += chunk.len() as u64;
+    }
+    Ok(read)
+}
+
+/// Adds the songs in `batch` that haven't been seen yet, up to `total` songs.
+fn add_unique(songs: &mut Vec<Song>, seen: &mut HashSet<String>, batch: Vec<Song>, total: usize) {
+    for song in batch {
+        if songs.len() < total && seen.insert(song.id.clone()) {
+            songs.push(song);
+        }
+    }
+}
+
 impl<'de> Deserialize<'de> for Song {
     fn deserialize<D>(de: D) -> ::std::result::Result<Self, D::Error>
     where
@@ -265,11 +812,15 @@ impl<'de> Deserialize<'de> for Song {
             // parent: String,
             // is_dir: bool,
             title: String,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
             album: Option<String>,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
             artist: Option<String>,
             track: Option<u64>,
             year: Option<u64>,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
             genre: Option<String>,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
             cover_art: Option<String>,
             size: u64,
             content_type: String,
@@ -277,20 +828,48 @@ impl<'de> Deserialize<'de> for Song {
             transcoded_content_type: Option<String>,
             transcoded_suffix: Option<String>,
             duration: Option<u64>,
-            // bit_rate: Option<u64>,
-            path: String,
-            // is_video: Option<bool>,
-            // play_count: u64,
+            bit_rate: Option<u64>,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+            path: Option<String>,
+            #[serde(default)]
+            is_video: bool,
+            play_count: Option<u64>,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+            played: Option<String>,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+            starred: Option<String>,
+            bookmark_position: Option<u64>,
             // disc_number: Option<u64>,
             // created: String,
             album_id: Option<String>,
             artist_id: Option<String>,
             #[serde(rename = "type")]
             media_type: String,
+            #[serde(default)]
+            artists: Vec<ArtistRef>,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+            display_artist: Option<String>,
+            #[serde(default)]
+            replay_gain: Option<ReplayGain>,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
+            music_brainz_id: Option<String>,
         }
 
         let raw = _Song::deserialize(de)?;
 
+        let artists = if raw.artists.is_empty() {
+            match (&raw.artist_id, &raw.artist) {
+                (Some(id), Some(name)) => vec![ArtistRef {
+                    id: id.clone(),
+                    name: name.clone(),
+                }],
+                _ => Vec::new(),
+            }
+        } else {
+            raw.artists
+        };
+        let display_artist = raw.display_artist.or_else(|| raw.artist.clone());
+
         Ok(Song {
             id: raw.id,
             title: raw.title,
@@ -298,6 +877,8 @@ impl<'de> Deserialize<'de> for Song {
             album_id: raw.album_id,
             artist: raw.artist,
             artist_id: raw.artist_id,
+            artists,
+            display_artist,
             cover_id: raw.cover_art,
             track: raw.track,
             year: raw.year,
@@ -308,8 +889,17 @@ impl<'de> Deserialize<'de> for Song {
             transcoded_content_type: raw.transcoded_content_type,
             transcoded_suffix: raw.transcoded_suffix,
             duration: raw.duration,
+            bit_rate: raw.bit_rate,
+            play_count: raw.play_count,
+            played: raw.played,
+            starred: raw.starred,
+            bookmark_position: raw.bookmark_position,
             path: raw.path,
             media_type: raw.media_type,
+            is_video: raw.is_video,
+            // Some servers send an empty object for songs never analysed.
+            replay_gain: raw.replay_gain.filter(|g| *g != ReplayGain::default()),
+            musicbrainz_id: raw.music_brainz_id,
             stream_br: None,
             stream_tc: None,
         })
@@ -317,7 +907,7 @@ impl<'de> Deserialize<'de> for Song {
 }
 
 /// A struct matching a lyric search result.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Lyrics {
     /// Title of the song.
     pub title: String,
@@ -459,6 +1049,19 @@ mod tests {
     use super::*;
     use crate::test_util;
 
+    #[test]
+    fn fixture_list_in_genre_ignores_case() {
+        let cli = test_util::fixture_site().unwrap();
+        let songs = tokio_test::block_on(Song::list_in_genre(
+            &cli,
+            "electronic",
+            SearchPage::new(),
+            None,
+        ))
+        .unwrap();
+        assert_eq!(songs.len(), 2);
+    }
+
     #[test]
     fn parse_song() {
         let parsed = serde_json::from_value::<Song>(raw()).unwrap();
@@ -466,6 +1069,279 @@ mod tests {
         assert_eq!(parsed.id, "27");
         assert_eq!(parsed.title, String::from("Bellevue Avenue"));
         assert_eq!(parsed.track, Some(1));
+        assert_eq!(parsed.play_count, Some(706));
+        assert_eq!(parsed.played, None);
+        assert_eq!(parsed.bookmark_position, None);
+        assert_eq!(
+            parsed.path.as_deref(),
+            Some("Misteur Valaire/Bellevue/01 - Misteur Valaire - Bellevue Avenue.mp3")
+        );
+    }
+
+    #[test]
+    fn serialize_song_round_trip() {
+        let parsed = serde_json::from_value::<Song>(raw()).unwrap();
+        let value = serde_json::to_value(&parsed).unwrap();
+
+        assert_eq!(value["coverArt"], "25");
+        assert_eq!(value["type"], "music");
+        assert!(value.get("streamBr").is_none());
+
+        let reparsed = serde_json::from_value::<Song>(value).unwrap();
+        assert_eq!(reparsed.id, parsed.id);
+        assert_eq!(reparsed.album_id, parsed.album_id);
+        assert_eq!(reparsed.duration, parsed.duration);
+    }
+
+    #[test]
+    fn parse_song_artists() {
+        let parsed = serde_json::from_value::<Song>(raw()).unwrap();
+        assert_eq!(parsed.display_artist.as_deref(), Some("Misteur Valaire"));
+        assert_eq!(parsed.artists.len(), 1);
+        assert_eq!(parsed.artists[0].id, "1");
+
+        let mut raw = raw();
+        raw["displayArtist"] = "Misteur Valaire feat. Karim Ouellet".into();
+        raw["artists"] = serde_json::json!([
+            { "id" : "1", "name" : "Misteur Valaire" },
+            { "id" : "9", "name" : "Karim Ouellet" }
+        ]);
+        let parsed = serde_json::from_value::<Song>(raw).unwrap();
+        assert_eq!(
+            parsed.display_artist.as_deref(),
+            Some("Misteur Valaire feat. Karim Ouellet")
+        );
+        assert_eq!(parsed.artists[1].name, "Karim Ouellet");
+    }
+
+    #[test]
+    fn predict_stream_suffix() {
+        let mut raw = raw();
+        raw["suffix"] = "flac".into();
+        raw["contentType"] = "audio/flac".into();
+        raw["bitRate"] = 1090.into();
+        let flac = serde_json::from_value::<Song>(raw.clone()).unwrap();
+
+        let params = StreamParams::new();
+        assert_eq!(flac.predicted_stream_suffix(&params), "flac");
+        let params = StreamParams::new()
+            .with_format("raw")
+            .with_max_bit_rate(128);
+        assert_eq!(flac.predicted_stream_suffix(&params), "flac");
+        let params = StreamParams::new().with_format("opus");
+        assert_eq!(flac.predicted_stream_suffix(&params), "opus");
+        let params = StreamParams::new().with_max_bit_rate(128);
+        assert_eq!(flac.predicted_stream_suffix(&params), "mp3");
+        let params = StreamParams::new().with_max_bit_rate(2000);
+        assert_eq!(flac.predicted_stream_suffix(&params), "flac");
+
+        raw["transcodedSuffix"] = "ogg".into();
+        let transcoded = serde_json::from_value::<Song>(raw).unwrap();
+        let params = StreamParams::new().with_max_bit_rate(128);
+        assert_eq!(transcoded.predicted_stream_suffix(&params), "ogg");
+    }
+
+    #[test]
+    fn parse_replay_gain() {
+        let mut raw = raw();
+        raw["replayGain"] = serde_json::json!({
+            "trackGain": -6.5,
+            "albumGain": -7.25,
+            "trackPeak": 0.98
+        });
+        let parsed = serde_json::from_value::<Song>(raw.clone()).unwrap();
+        let gain = parsed.replay_gain.unwrap();
+        assert_eq!(gain.track_gain, Some(-6.5));
+        assert_eq!(gain.album_peak, None);
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap()["replayGain"],
+            raw["replayGain"]
+        );
+
+        raw["replayGain"] = serde_json::json!({});
+        let parsed = serde_json::from_value::<Song>(raw).unwrap();
+        assert_eq!(parsed.replay_gain, None);
+        assert!(serde_json::from_value::<Song>(self::raw())
+            .unwrap()
+            .replay_gain
+            .is_none());
+    }
+
+    #[test]
+    fn parse_bookmark_position() {
+        let mut raw = raw();
+        raw["bookmarkPosition"] = 4_980_000.into();
+        let parsed = serde_json::from_value::<Song>(raw).unwrap();
+
+        assert_eq!(parsed.bookmark_position, Some(4_980_000));
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap()["bookmarkPosition"],
+            4_980_000
+        );
+    }
+
+    #[test]
+    fn parse_song_blank_fields() {
+        let mut raw = raw();
+        raw["genre"] = "".into();
+        raw["coverArt"] = serde_json::Value::Null;
+        let parsed = serde_json::from_value::<Song>(raw).unwrap();
+
+        assert_eq!(parsed.genre, None);
+        assert!(!parsed.has_cover_art());
+    }
+
+    #[test]
+    fn fixture_stream_with() {
+        let cli = test_util::fixture_site().unwrap();
+        let song = serde_json::from_value::<Song>(raw()).unwrap();
+        let params = StreamParams::new();
+
+        let mut received = Vec::new();
+        let read = tokio_test::block_on(song.stream_with(&cli, &params, |chunk| {
+            received.extend_from_slice(chunk);
+            Ok(())
+        }))
+        .unwrap();
+        assert_eq!(received, b"ID3 not really an mp3\n");
+        assert_eq!(read, received.len() as u64);
+
+        let stopped = tokio_test::block_on(
+            song.stream_with(&cli, &params, |_| Err(Error::Other("sink full"))),
+        );
+        assert!(matches!(stopped, Err(Error::Other("sink full"))));
+    }
+
+    #[test]
+    fn fixture_toggle_star() {
+        let cli = test_util::fixture_site().unwrap();
+        let starred = serde_json::from_value::<Song>(raw()).unwrap();
+        assert!(starred.starred.is_some());
+        assert!(!tokio_test::block_on(starred.toggle_star(&cli)).unwrap());
+
+        let mut raw = raw();
+        raw["starred"] = "".into();
+        let unstarred = serde_json::from_value::<Song>(raw).unwrap();
+        assert!(unstarred.starred.is_none());
+        assert!(tokio_test::block_on(unstarred.toggle_star(&cli)).unwrap());
+    }
+
+    #[test]
+    fn song_or_album_cover_art() {
+        let cli = Client::new("http://example.com", "guest", "guest").unwrap();
+
+        let parsed = serde_json::from_value::<Song>(raw()).unwrap();
+        let url = tokio_test::block_on(parsed.best_cover_art_url(&cli, 300)).unwrap();
+        assert!(url.contains("getCoverArt?"));
+        assert!(url.contains("id=25&size=300"));
+
+        let mut raw = raw();
+        raw["coverArt"] = serde_json::Value::Null;
+        raw["albumId"] = serde_json::Value::Null;
+        let loose = serde_json::from_value::<Song>(raw).unwrap();
+        assert!(tokio_test::block_on(loose.best_cover_art_url(&cli, None)).is_err());
+    }
+
+    #[test]
+    fn best_stream_format() {
+        use StreamFormat::*;
+
+        let parsed = serde_json::from_value::<Song>(raw()).unwrap();
+        assert_eq!(parsed.suffix, "mp3");
+        assert_eq!(parsed.best_format(&[Opus, Mp3]), Some(Raw));
+        assert_eq!(parsed.best_format(&[Opus, Raw]), Some(Raw));
+        assert_eq!(parsed.best_format(&[Opus, Aac]), Some(Opus));
+        assert_eq!(parsed.best_format(&[]), None);
+
+        let mut flac = raw();
+        flac["suffix"] = "".into();
+        flac["contentType"] = "audio/flac".into();
+        let flac = serde_json::from_value::<Song>(flac).unwrap();
+        assert_eq!(flac.best_format(&[Mp3, Flac]), Some(Raw));
+    }
+
+    #[test]
+    fn song_media_kind() {
+        let parsed = serde_json::from_value::<Song>(raw()).unwrap();
+        assert!(!parsed.is_video);
+        assert_eq!(parsed.media_kind(), MediaKind::Audio);
+
+        let mut video = raw();
+        video["isVideo"] = true.into();
+        let video = serde_json::from_value::<Song>(video).unwrap();
+        assert_eq!(video.media_kind(), MediaKind::Video);
+
+        let mut podcast = raw();
+        podcast["type"] = "podcast".into();
+        let podcast = serde_json::from_value::<Song>(podcast).unwrap();
+        assert_eq!(podcast.media_kind(), MediaKind::Podcast);
+    }
+
+    #[test]
+    fn audio_policy_requests() {
+        let parsed = serde_json::from_value::<Song>(raw()).unwrap();
+        assert_eq!(parsed.bit_rate, Some(216));
+
+        let download = ("download", Query::with("id", "27"));
+        let opus = StreamParams::new().with_format("opus");
+        let capped = StreamParams::new().with_max_bit_rate(128);
+
+        assert_eq!(parsed.audio_request(&AudioPolicy::Original), download);
+        assert_eq!(
+            parsed.audio_request(&AudioPolicy::Transcode(opus.clone())),
+            ("stream", opus.to_query("27"))
+        );
+        assert_eq!(
+            parsed.audio_request(&AudioPolicy::PreferOriginalUnder(320)),
+            download
+        );
+        assert_eq!(
+            parsed.audio_request(&AudioPolicy::PreferOriginalUnder(128)),
+            ("stream", capped.to_query("27"))
+        );
+    }
+
+    #[test]
+    fn add_unique_random_songs() {
+        let song = |id: &str| {
+            let mut raw = raw();
+            raw["id"] = id.into();
+            serde_json::from_value::<Song>(raw).unwrap()
+        };
+
+        let mut seen = HashSet::new();
+        let mut songs = Vec::new();
+        add_unique(&mut songs, &mut seen, vec![song("1"), song("2")], 3);
+        add_unique(
+            &mut songs,
+            &mut seen,
+            vec![song("2"), song("3"), song("4")],
+            3,
+        );
+
+        let ids: Vec<_> = songs.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["1", "2", "3"]);
+    }
+
+    #[test]
+    fn song_filename() {
+        let parsed = serde_json::from_value::<Song>(raw()).unwrap();
+        assert_eq!(
+            parsed.suggested_filename(),
+            "01 - Misteur Valaire - Bellevue Avenue.mp3"
+        );
+
+        let mut raw = raw();
+        raw["title"] = "What? / Why: \"Not\"...".into();
+        raw["suffix"] = "".into();
+        raw["contentType"] = "audio/flac".into();
+        raw["track"] = serde_json::Value::Null;
+        raw["artist"] = serde_json::Value::Null;
+        let parsed = serde_json::from_value::<Song>(raw).unwrap();
+        assert_eq!(parsed.suggested_filename(), "What_ _ Why_ _Not_.flac");
+
+        assert_eq!(crate::util::sanitize_file_stem("con"), "_con");
+        assert_eq!(crate::util::sanitize_file_stem(" . "), "_");
     }
 
     #[test]
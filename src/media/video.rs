@@ -6,36 +6,54 @@ use serde::de::{Deserialize, Deserializer};
 use serde_json;
 
 use crate::query::Query;
-use crate::{Client, Error, Media, Result, Streamable};
+use crate::units::Seconds;
+use crate::{Client, Error, HlsPlaylist, Media, Result, Streamable};
 
 #[allow(missing_docs)]
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 #[readonly::make]
 pub struct Video {
     pub id: String,
+    #[serde(serialize_with = "crate::util::serialize_display")]
     pub parent: usize,
     pub is_dir: bool,
     pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub album: Option<String>,
+    #[serde(rename = "coverArt")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub cover_id: Option<String>,
     pub size: usize,
     pub content_type: String,
     pub suffix: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub transcoded_suffix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub transcoded_content_type: Option<String>,
     pub duration: usize,
+    #[serde(rename = "bitRate")]
     pub bitrate: usize,
     pub path: String,
     pub is_video: bool,
     pub created: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub play_count: Option<u64>,
+    #[serde(rename = "type")]
     pub media_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub bookmark_position: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub original_height: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub original_width: Option<u64>,
+    #[serde(skip)]
     pub stream_br: Option<usize>,
+    #[serde(skip)]
     pub stream_size: Option<(usize, usize)>,
-    pub stream_offset: usize,
+    #[serde(skip)]
+    pub stream_offset: Seconds,
+    #[serde(skip)]
     pub stream_tc: Option<String>,
 }
 
@@ -67,6 +85,47 @@ impl Video {
         Ok(serde_json::from_value(res)?)
     }
 
+    /// Returns an HLS playlist for the video, optionally with the audio track
+    /// to play.
+    ///
+    /// The available audio tracks are listed in the video's [`info`]; without
+    /// one, the server picks its default track.
+    ///
+    /// [`info`]: #method.info
+    pub async fn hls<A>(
+        &self,
+        client: &Client,
+        bit_rates: &[u64],
+        audio_track: A,
+    ) -> Result<HlsPlaylist>
+    where
+        A: Into<Option<usize>>,
+    {
+        let args = self.hls_query(bit_rates, audio_track.into());
+        let raw = client.get_raw("hls", args).await?;
+        raw.parse::<HlsPlaylist>()
+    }
+
+    /// Returns the URL of an HLS playlist for the video, optionally with the
+    /// audio track to play.
+    ///
+    /// See [`hls`] for how the audio track is chosen.
+    ///
+    /// [`hls`]: #method.hls
+    pub fn hls_url<A>(&self, client: &Client, bit_rates: &[u64], audio_track: A) -> Result<String>
+    where
+        A: Into<Option<usize>>,
+    {
+        client.build_url("hls", self.hls_query(bit_rates, audio_track.into()))
+    }
+
+    fn hls_query(&self, bit_rates: &[u64], audio_track: Option<usize>) -> Query {
+        Query::with("id", self.id.as_ref())
+            .arg_list("bitRate", bit_rates)
+            .arg("audioTrack", audio_track)
+            .build()
+    }
+
     /// Returns the raw video captions.
     pub async fn captions<'a, S>(&self, client: &Client, format: S) -> Result<String>
     where
@@ -84,12 +143,13 @@ impl Video {
         self.stream_size = Some((width, height));
     }
 
-    /// Sets the time (in seconds) that a stream will be offset by.
+    /// Sets the time that a stream will be offset by.
     ///
-    /// For example, to start playback at 1:40, use an offset of 100 seconds.
+    /// For example, to start playback at 1:40, use an offset of
+    /// `Seconds(100)`.
     ///
     /// Can be used to implement video skipping.
-    pub async fn set_start_time(&mut self, offset: usize) {
+    pub async fn set_start_time(&mut self, offset: Seconds) {
         self.stream_offset = offset;
     }
 }
@@ -180,7 +240,9 @@ impl<'de> Deserialize<'de> for Video {
             parent: String,
             is_dir: bool,
             title: String,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
             album: Option<String>,
+            #[serde(default, deserialize_with = "crate::util::empty_as_none")]
             cover_art: Option<String>,
             size: usize,
             content_type: String,
@@ -226,19 +288,37 @@ impl<'de> Deserialize<'de> for Video {
             original_width: raw.original_width,
             stream_br: None,
             stream_size: None,
-            stream_offset: 0,
+            stream_offset: Seconds(0),
             stream_tc: None,
         })
     }
 }
 
+/// The audio tracks, captions, and conversions available for a video.
+///
+/// Fetched with [`Video::info`]; the IDs of the audio tracks can be passed to
+/// [`Video::hls`] and [`Video::hls_url`].
+///
+/// [`Video::info`]: struct.Video.html#method.info
+/// [`Video::hls`]: struct.Video.html#method.hls
+/// [`Video::hls_url`]: struct.Video.html#method.hls_url
 #[allow(missing_docs)]
 #[derive(Debug)]
 pub struct VideoInfo {
     pub id: usize,
-    pub captions: Option<Captions>,
+    pub captions: Vec<Caption>,
     pub audio_tracks: Vec<AudioTrack>,
-    pub conversion: Option<Conversion>,
+    pub conversions: Vec<Conversion>,
+}
+
+impl VideoInfo {
+    /// Returns the first audio track in the language, given as an ISO 639-2
+    /// code such as `"eng"`.
+    pub fn audio_track(&self, language_code: &str) -> Option<&AudioTrack> {
+        self.audio_tracks
+            .iter()
+            .find(|t| t.language_code.eq_ignore_ascii_case(language_code))
+    }
 }
 
 impl<'de> Deserialize<'de> for VideoInfo {
@@ -249,18 +329,20 @@ impl<'de> Deserialize<'de> for VideoInfo {
         #[derive(Deserialize)]
         struct _VideoInfo {
             id: String,
-            captions: Option<Captions>,
+            #[serde(default, deserialize_with = "crate::util::one_or_many")]
+            captions: Vec<Caption>,
             #[serde(rename = "audioTrack")]
-            #[serde(default)]
+            #[serde(default, deserialize_with = "crate::util::one_or_many")]
             audio_tracks: Vec<AudioTrack>,
-            conversion: Option<Conversion>,
+            #[serde(default, deserialize_with = "crate::util::one_or_many")]
+            conversion: Vec<Conversion>,
         }
         let raw = _VideoInfo::deserialize(de)?;
         Ok(VideoInfo {
             id: raw.id.parse().unwrap(),
             captions: raw.captions,
             audio_tracks: raw.audio_tracks,
-            conversion: raw.conversion,
+            conversions: raw.conversion,
         })
     }
 }
@@ -296,23 +378,23 @@ impl<'de> Deserialize<'de> for AudioTrack {
 
 #[allow(missing_docs)]
 #[derive(Debug)]
-pub struct Captions {
+pub struct Caption {
     pub id: usize,
     pub name: String,
 }
 
-impl<'de> Deserialize<'de> for Captions {
+impl<'de> Deserialize<'de> for Caption {
     fn deserialize<D>(de: D) -> result::Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
         #[derive(Deserialize)]
-        struct _Captions {
+        struct _Caption {
             id: String,
             name: String,
         }
-        let raw = _Captions::deserialize(de)?;
-        Ok(Captions {
+        let raw = _Caption::deserialize(de)?;
+        Ok(Caption {
             id: raw.id.parse().unwrap(),
             name: raw.name,
         })
@@ -364,6 +446,25 @@ mod tests {
 
         assert_eq!(parsed.id, 7058);
         assert_eq!(parsed.audio_tracks.len(), 5);
+        assert_eq!(parsed.captions.len(), 1);
+        assert_eq!(parsed.captions[0].name, "Planes 2.srt");
+        assert_eq!(parsed.conversions.len(), 1);
+        assert_eq!(parsed.conversions[0].bitrate, 1000);
+        assert_eq!(parsed.audio_track("DAN").map(|t| t.id), Some(3));
+        assert!(parsed.audio_track("deu").is_none());
+    }
+
+    #[test]
+    fn hls_url_with_audio_track() {
+        let cli = Client::new("http://localhost", "guest", "guest").unwrap();
+        let video = serde_json::from_value::<Video>(raw()).unwrap();
+
+        let url = video.hls_url(&cli, &[1000], 3).unwrap();
+        assert!(url.contains("hls?"));
+        assert!(url.contains("id=460&bitRate=1000&audioTrack=3"));
+
+        let url = video.hls_url(&cli, &[], None).unwrap();
+        assert!(!url.contains("audioTrack"));
     }
 
     fn raw() -> serde_json::Value {
@@ -0,0 +1,120 @@
+//! Counters of the requests a client makes.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::Error;
+
+/// A snapshot of the requests a [`Client`] has made.
+///
+/// Taken with [`Client::metrics`]. The counters only ever increase, so the
+/// difference between two snapshots gives the activity in between.
+///
+/// [`Client`]: ./struct.Client.html
+/// [`Client::metrics`]: ./struct.Client.html#method.metrics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Metrics {
+    /// Requests sent, counting each redirect followed.
+    pub requests: u64,
+    /// Bytes of response bodies received.
+    pub bytes_downloaded: u64,
+    /// Requests that failed to connect, or that the server answered with an
+    /// HTTP error or an HTML page.
+    pub connection_errors: u64,
+    /// Requests that the server answered with a Subsonic API error.
+    pub api_errors: u64,
+    /// Responses that couldn't be parsed.
+    pub parse_errors: u64,
+    /// Requests that failed for any other reason.
+    pub other_errors: u64,
+    /// The average time taken for the server to start responding.
+    pub average_latency: Duration,
+}
+
+impl Metrics {
+    /// Returns the number of failed requests of all kinds.
+    pub fn errors(&self) -> u64 {
+        self.connection_errors + self.api_errors + self.parse_errors + self.other_errors
+    }
+}
+
+/// The live counters behind [`Metrics`], shared with streams that are still
+/// being read.
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    requests: AtomicU64,
+    bytes_downloaded: AtomicU64,
+    connection_errors: AtomicU64,
+    api_errors: AtomicU64,
+    parse_errors: AtomicU64,
+    other_errors: AtomicU64,
+    latency_micros: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn request(&self, latency: Duration) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.latency_micros
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn downloaded(&self, bytes: usize) {
+        self.bytes_downloaded
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn error(&self, err: &Error) {
+        let counter = match *err {
+            Error::Connection(_) | Error::Html(_) | Error::Reqwest(_) => &self.connection_errors,
+            Error::Api(..) => &self.api_errors,
+            Error::Serde(_) | Error::Parse(_) | Error::InvalidResponse(_) => &self.parse_errors,
+            _ => &self.other_errors,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> Metrics {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let latency = self.latency_micros.load(Ordering::Relaxed);
+        Metrics {
+            requests,
+            bytes_downloaded: self.bytes_downloaded.load(Ordering::Relaxed),
+            connection_errors: self.connection_errors.load(Ordering::Relaxed),
+            api_errors: self.api_errors.load(Ordering::Relaxed),
+            parse_errors: self.parse_errors.load(Ordering::Relaxed),
+            other_errors: self.other_errors.load(Ordering::Relaxed),
+            average_latency: match requests {
+                0 => Duration::ZERO,
+                n => Duration::from_micros(latency / n),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ApiError;
+
+    #[test]
+    fn count_requests_and_errors() {
+        let counters = Counters::default();
+        assert_eq!(counters.snapshot(), Metrics::default());
+
+        counters.request(Duration::from_millis(10));
+        counters.request(Duration::from_millis(30));
+        counters.downloaded(512);
+        counters.error(&ApiError::NotFound.into());
+        counters.error(&Error::InvalidResponse(String::new()));
+        counters.error(&Error::Other("failed"));
+
+        let metrics = counters.snapshot();
+        assert_eq!(metrics.requests, 2);
+        assert_eq!(metrics.bytes_downloaded, 512);
+        assert_eq!(metrics.api_errors, 1);
+        assert_eq!(metrics.parse_errors, 1);
+        assert_eq!(metrics.other_errors, 1);
+        assert_eq!(metrics.errors(), 3);
+        assert_eq!(metrics.average_latency, Duration::from_millis(20));
+    }
+}
@@ -2,6 +2,14 @@
 
 use std::{fmt, iter};
 
+use crate::units::{Millis, Seconds};
+
+/// Keys whose values are masked by [`Query::debug_redacted`]: the
+/// authentication parameters, and the passwords set by user management.
+///
+/// [`Query::debug_redacted`]: ./struct.Query.html#method.debug_redacted
+pub const SECRET_KEYS: &[&str] = &["t", "s", "p", "apiKey", "password"];
+
 /// An expandable query set for an API call.
 #[derive(Debug, PartialEq, PartialOrd)]
 pub struct Query {
@@ -92,6 +100,106 @@ impl Query {
         self
     }
 
+    /// Adds arbitrary key-value pairs to the query.
+    ///
+    /// This is an escape hatch for parameters that `sunk` doesn't model, such
+    /// as those added by forks of Subsonic. Like any other argument, the
+    /// pairs are URL-encoded when the query is sent.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// # use sunk::query::Query;
+    /// let query = Query::with("id", 64).extra(&[("_", "1518134400")]).build();
+    /// assert_eq!(query.to_string(), "id=64&_=1518134400");
+    /// ```
+    pub fn extra(&mut self, pairs: &[(&str, &str)]) -> &mut Query {
+        for (k, v) in pairs {
+            self.inner.push((k.to_string(), v.into_arg()));
+        }
+        self
+    }
+
+    /// Adds the arguments of another query to this one.
+    ///
+    /// This allows a common base query to be reused across requests. If
+    /// `replace` is set, arguments in `other` replace any in this query with
+    /// the same key; otherwise both are kept, as with [`arg_list`].
+    ///
+    /// [`arg_list`]: #method.arg_list
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// # use sunk::query::Query;
+    /// let base = Query::new().arg("musicFolderId", 3).arg("size", 20).build();
+    ///
+    /// let query = Query::with("size", 50).merge(base, false).build();
+    /// assert_eq!(query.to_string(), "size=50&musicFolderId=3&size=20");
+    /// ```
+    pub fn merge(&mut self, other: Query, replace: bool) -> &mut Query {
+        if replace {
+            self.inner
+                .retain(|(k, _)| !other.inner.iter().any(|(o, a)| o == k && a.is_some()));
+        }
+        self.inner.extend(other.inner);
+        self
+    }
+
+    /// Returns the query as it would be sent, with the values of
+    /// [`SECRET_KEYS`] masked, for logging.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sunk::query::Query;
+    /// let query = Query::with("username", "guest").arg("password", "hunter2").build();
+    /// assert_eq!(query.debug_redacted(), "username=guest&password=<redacted>");
+    /// ```
+    ///
+    /// [`SECRET_KEYS`]: ./constant.SECRET_KEYS.html
+    pub fn debug_redacted(&self) -> String {
+        self.debug_redacted_with(SECRET_KEYS)
+    }
+
+    /// Returns the query as it would be sent, with the values of the given
+    /// keys masked, for logging.
+    pub fn debug_redacted_with(&self, secrets: &[&str]) -> String {
+        self.pairs()
+            .map(|(k, v)| {
+                if secrets.contains(&k) {
+                    format!("{}=<redacted>", encode(k))
+                } else {
+                    format!("{}={}", encode(k), encode(v))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    /// Returns whether the query would send no parameters at all, such as
+    /// when every argument is unset.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sunk::query::Query;
+    /// assert!(Query::none().is_empty());
+    /// assert!(Query::with("size", None::<usize>).is_empty());
+    /// assert!(!Query::with("size", 20).is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.pairs().next().is_none()
+    }
+
+    /// Returns the arguments that are sent: those with a key and a value.
+    fn pairs(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.inner
+            .iter()
+            .filter(|(k, _)| !k.is_empty())
+            .filter_map(|(k, a)| Some((k.as_str(), a.0.as_deref()?)))
+    }
+
     /// Consumes the query builder and returns a completed query.
     pub fn build(&mut self) -> Query {
         Query {
@@ -108,18 +216,21 @@ impl iter::Extend<(String, Arg)> for Query {
 
 impl fmt::Display for Query {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for (n, a) in self.inner.iter().enumerate() {
-            if a.1.is_some() {
-                write!(f, "{}={}", a.0, a.1)?;
-                if n + 1 < self.inner.len() {
-                    write!(f, "&")?;
-                }
+        for (n, (k, v)) in self.pairs().enumerate() {
+            if n > 0 {
+                f.write_str("&")?;
             }
+            write!(f, "{}={}", encode(k), encode(v))?;
         }
         Ok(())
     }
 }
 
+/// Percent-encodes a query key or value.
+fn encode(s: &str) -> String {
+    url::form_urlencoded::byte_serialize(s.as_bytes()).collect()
+}
+
 impl Default for Query {
     fn default() -> Query {
         Query::new()
@@ -138,10 +249,9 @@ impl Arg {
 
 impl fmt::Display for Arg {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self.is_some() {
-            write!(f, "{}", self.0.clone().unwrap())
-        } else {
-            write!(f, "")
+        match self.0 {
+            Some(ref s) => write!(f, "{s}"),
+            None => Ok(()),
         }
     }
 }
@@ -193,8 +303,10 @@ impl_arg!(u32);
 impl_arg!(u64);
 impl_arg!(usize);
 impl_arg!(bool);
+impl_arg!(Seconds);
+impl_arg!(Millis);
 
-impl<'a> IntoArg for &'a str {
+impl IntoArg for &str {
     fn into_arg(self) -> Arg {
         Arg(Some(self.to_owned()))
     }
@@ -210,6 +322,24 @@ impl IntoArg for String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn redact_secrets() {
+        let query = Query::with("u", "guest")
+            .arg("t", "26719a")
+            .arg("s", "c19b2d")
+            .arg("size", None::<usize>)
+            .arg("name", "R&B")
+            .build();
+        assert_eq!(
+            query.debug_redacted(),
+            "u=guest&t=<redacted>&s=<redacted>&name=R%26B"
+        );
+        assert_eq!(
+            query.debug_redacted_with(&["u"]),
+            "u=<redacted>&t=26719a&s=c19b2d&name=R%26B"
+        );
+    }
+
     #[test]
     fn empty_query_is_empty() {
         let q = Query::none();
@@ -236,6 +366,25 @@ mod tests {
         assert_eq!("id=64", &format!("{q}"));
     }
 
+    #[test]
+    fn unset_args_leave_no_separators() {
+        let q = Query::with("id", 64).arg("size", None::<usize>).build();
+        assert_eq!("id=64", &format!("{q}"));
+        assert!(!q.is_empty());
+
+        let q = Query::with("", "").arg("size", None::<usize>).build();
+        assert_eq!("", &format!("{q}"));
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn query_is_encoded() {
+        let q = Query::with("query", "AC/DC & friends")
+            .extra(&[("_", "1.0")])
+            .build();
+        assert_eq!("query=AC%2FDC+%26+friends&_=1.0", &format!("{q}"))
+    }
+
     #[test]
     fn query_vec() {
         let ids = &[1, 2, 3, 4];
@@ -243,4 +392,26 @@ mod tests {
         q.arg_list("id", ids);
         assert_eq!("id=1&id=2&id=3&id=4", &format!("{q}"))
     }
+
+    #[test]
+    fn merge_queries() {
+        let base = || Query::new().arg("musicFolderId", 3).arg("size", 20).build();
+
+        let appended = Query::with("size", 50).merge(base(), false).build();
+        assert_eq!(appended.to_string(), "size=50&musicFolderId=3&size=20");
+
+        let replaced = Query::with("size", 50)
+            .arg("offset", 10)
+            .merge(base(), true)
+            .build();
+        assert_eq!(replaced.to_string(), "offset=10&musicFolderId=3&size=20");
+
+        let unset = Query::with("size", 50)
+            .merge(
+                Query::with("size", None::<u32>).arg("offset", 10).build(),
+                true,
+            )
+            .build();
+        assert_eq!(unset.to_string(), "size=50&offset=10");
+    }
 }
@@ -1,23 +1,87 @@
 //! Search response APIs.
 
+use std::fmt;
+use std::marker::PhantomData;
+use std::result;
+
+use serde::de::{
+    self, Deserialize, DeserializeOwned, DeserializeSeed, Deserializer, IgnoredAny, MapAccess,
+    SeqAccess, Visitor,
+};
 use serde_json;
 
-use crate::ApiError;
+use crate::{ApiError, Error, Result, Version};
+
+/// How much of an unrecognised body to include in an error.
+const PREVIEW_LEN: usize = 200;
+const TRUNCATED: &str = "incomplete response body — connection likely dropped";
+/// The key of the object that holds a response.
+const ENVELOPE: &str = "subsonic-response";
 
 /// A top-level response from a Subsonic server.
-#[derive(Deserialize)]
+///
+/// The response is found by its `subsonic-response` key, so any keys sent
+/// alongside it are ignored. Bodies that nest that key one level down, or
+/// that leave it out and send the response's own keys at the top level, are
+/// accepted too.
 pub struct Response {
-    #[serde(rename = "subsonic-response")]
     inner: InnerResponse,
 }
 
+impl<'de> Deserialize<'de> for Response {
+    fn deserialize<D>(de: D) -> result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(de)?;
+        let inner = envelope(value).ok_or_else(|| de::Error::missing_field(ENVELOPE))?;
+        Ok(Response {
+            inner: InnerResponse::deserialize(inner).map_err(de::Error::custom)?,
+        })
+    }
+}
+
+/// Finds the object holding the response in a parsed body.
+///
+/// Servers normally send it as the `subsonic-response` key of the body, but
+/// some proxies nest the body one level further down, and a few servers send
+/// the response's own keys at the top level instead. These are told apart
+/// from other JSON by having a `version` and a `status` of `ok` or `failed`.
+fn envelope(value: serde_json::Value) -> Option<serde_json::Value> {
+    let mut map = match value {
+        serde_json::Value::Object(map) => map,
+        _ => return None,
+    };
+    if let Some(inner) = map.remove(ENVELOPE) {
+        return Some(inner).filter(serde_json::Value::is_object);
+    }
+    let status = map.get("status").and_then(serde_json::Value::as_str);
+    if matches!(status, Some("ok" | "failed")) && map.contains_key("version") {
+        return Some(serde_json::Value::Object(map));
+    }
+    map.into_iter()
+        .find_map(|(_, mut v)| v.get_mut(ENVELOPE).map(serde_json::Value::take))
+        .filter(serde_json::Value::is_object)
+}
+
+/// The `error` object of a failed response.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ErrorBody {
+    #[serde(flatten)]
+    kind: ApiError,
+    help_url: Option<String>,
+}
+
 /// A struct containing the possible responses of the Subsonic API.
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct InnerResponse {
     // status: String,
-    // version: String,
-    error: Option<ApiError>,
+    version: Option<String>,
+    #[serde(rename = "type")]
+    server_type: Option<String>,
+    error: Option<ErrorBody>,
     license: Option<serde_json::Value>,
     music_folders: Option<serde_json::Value>,
     indexes: Option<serde_json::Value>,
@@ -41,6 +105,7 @@ struct InnerResponse {
     random_songs: Option<serde_json::Value>,
     songs_by_genre: Option<serde_json::Value>,
     now_playing: Option<serde_json::Value>,
+    open_subsonic_extensions: Option<serde_json::Value>,
     starred: Option<serde_json::Value>,
     starred2: Option<serde_json::Value>,
     search_result: Option<serde_json::Value>,
@@ -53,6 +118,7 @@ struct InnerResponse {
     podcasts: Option<serde_json::Value>,
     newest_podcasts: Option<serde_json::Value>,
     jukebox_status: Option<serde_json::Value>,
+    token_info: Option<serde_json::Value>,
     jukebox_playlist: Option<serde_json::Value>,
     internet_radio_stations: Option<serde_json::Value>,
     chat_messages: Option<serde_json::Value>,
@@ -64,6 +130,47 @@ struct InnerResponse {
 }
 
 impl Response {
+    /// Parses the body of a response.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::InvalidResponse`] holding the start of the body if
+    /// it isn't a Subsonic response at all, such as a proxy's login page.
+    ///
+    /// Returns an [`Error::Other`] if the body stops partway through, which
+    /// usually means the connection dropped and the request can be retried.
+    ///
+    /// [`Error::InvalidResponse`]: ../enum.Error.html#variant.InvalidResponse
+    /// [`Error::Other`]: ../enum.Error.html#variant.Other
+    pub fn parse(body: &str) -> Result<Response> {
+        let value = match serde_json::from_str(body) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                check_truncated(&e, body)?;
+                None
+            }
+        };
+        Response::from_value(value, body)
+    }
+
+    /// Parses the body of a response sent in XML.
+    ///
+    /// # Errors
+    ///
+    /// Has the same error conditions as [`parse`](#method.parse).
+    pub fn parse_xml(body: &str) -> Result<Response> {
+        Response::from_value(crate::xml::to_json(body), body)
+    }
+
+    fn from_value(value: Option<serde_json::Value>, body: &str) -> Result<Response> {
+        let inner = value
+            .and_then(envelope)
+            .ok_or_else(|| Error::InvalidResponse(preview(body)))?;
+        Ok(Response {
+            inner: serde_json::from_value(inner)?,
+        })
+    }
+
     /// Extracts the internal value of the response.
     ///
     /// # Errors
@@ -110,6 +217,7 @@ impl Response {
             music_folders,
             newest_podcasts,
             now_playing,
+            open_subsonic_extensions,
             play_queue,
             playlist,
             playlists,
@@ -126,6 +234,7 @@ impl Response {
             songs_by_genre,
             starred,
             starred2,
+            token_info,
             top_songs,
             user,
             users,
@@ -135,10 +244,27 @@ impl Response {
         None
     }
 
+    /// Returns the API version the server reported, if any.
+    pub fn version(&self) -> Option<Version> {
+        self.inner.version.as_deref().map(Version::from)
+    }
+
+    /// Returns the name of the server software, if the server reported it.
+    /// Only sent by OpenSubsonic servers.
+    pub fn server_type(&self) -> Option<&str> {
+        self.inner.server_type.as_deref()
+    }
+
     /// Extracts the error struct of the response. Returns `None` if the
     /// response was not a failure.
     pub fn into_error(self) -> Option<ApiError> {
-        self.inner.error
+        self.inner.error.map(|e| e.kind)
+    }
+
+    /// Returns the link to more information about the error, if the response
+    /// was a failure and the server sent one.
+    pub fn help_url(&self) -> Option<&str> {
+        self.inner.error.as_ref()?.help_url.as_deref()
     }
 
     /// Returns `true` if the response is `"ok"`.
@@ -156,9 +282,221 @@ impl Response {
     // self.into_value().is_none() }
 }
 
+/// Parses the items at `path` in a response body one at a time, handing each
+/// to `f`, without building the whole response as a `serde_json::Value`
+/// first. The body itself must already be in memory.
+///
+/// `path` is the chain of keys below `subsonic-response` leading to the
+/// items, such as `["artists", "index", "artist"]`. Lists met along the way
+/// are walked element by element. XML bodies are converted to JSON first, so
+/// only JSON bodies save memory.
+pub(crate) fn parse_items<T, F>(body: &str, xml: bool, path: &[&str], mut f: F) -> Result<()>
+where
+    T: DeserializeOwned,
+    F: FnMut(T),
+{
+    #[derive(Deserialize)]
+    struct Status {
+        #[serde(rename = "subsonic-response")]
+        inner: StatusInner,
+    }
+
+    #[derive(Deserialize)]
+    struct StatusInner {
+        error: Option<ErrorBody>,
+    }
+
+    let mut value = if xml {
+        Some(crate::xml::to_json(body).ok_or_else(|| Error::InvalidResponse(preview(body)))?)
+    } else {
+        None
+    };
+
+    // A first pass skims the body for an error, skipping everything else.
+    let status = match value {
+        Some(ref v) => Status::deserialize(v).ok(),
+        None => match serde_json::from_str::<Status>(body) {
+            Ok(s) => Some(s),
+            Err(e) => {
+                check_truncated(&e, body)?;
+                None
+            }
+        },
+    };
+    // Bodies that aren't shaped as usual are parsed in full to find the
+    // response, then walked in memory like XML ones.
+    let status = match status {
+        Some(status) => status,
+        None => {
+            let inner = value
+                .take()
+                .or_else(|| serde_json::from_str(body).ok())
+                .and_then(envelope)
+                .ok_or_else(|| Error::InvalidResponse(preview(body)))?;
+            let wrapped = serde_json::json!({ ENVELOPE: inner });
+            let status =
+                Status::deserialize(&wrapped).map_err(|_| Error::InvalidResponse(preview(body)))?;
+            value = Some(wrapped);
+            status
+        }
+    };
+    if let Some(e) = status.inner.error {
+        return Err(Error::Api(e.kind, e.help_url));
+    }
+
+    let mut full_path = vec![ENVELOPE];
+    full_path.extend_from_slice(path);
+    let walk = Walk {
+        path: &full_path,
+        f: &mut f,
+        item: PhantomData,
+    };
+    match value {
+        Some(ref v) => walk.deserialize(v)?,
+        None => walk.deserialize(&mut serde_json::Deserializer::from_str(body))?,
+    }
+    Ok(())
+}
+
+/// Fails if a JSON error came from the body ending early, rather than being
+/// malformed. Empty bodies are left to be reported as invalid.
+fn check_truncated(err: &serde_json::Error, body: &str) -> Result<()> {
+    if err.is_eof() && !body.trim().is_empty() {
+        Err(Error::Other(TRUNCATED))
+    } else {
+        Ok(())
+    }
+}
+
+/// Follows the rest of a path through a response, handing the items at its
+/// end to `f`.
+struct Walk<'a, T, F> {
+    path: &'a [&'a str],
+    f: &'a mut F,
+    item: PhantomData<T>,
+}
+
+impl<'de, 'a, T, F> DeserializeSeed<'de> for Walk<'a, T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(T),
+{
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, de: D) -> result::Result<(), D::Error> {
+        de.deserialize_any(self)
+    }
+}
+
+impl<'de, 'a, T, F> Visitor<'de> for Walk<'a, T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(T),
+{
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a list or object")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> result::Result<(), A::Error> {
+        if self.path.is_empty() {
+            while let Some(item) = seq.next_element::<T>()? {
+                (self.f)(item);
+            }
+        } else {
+            while seq
+                .next_element_seed(Walk {
+                    path: self.path,
+                    f: &mut *self.f,
+                    item: PhantomData,
+                })?
+                .is_some()
+            {}
+        }
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> result::Result<(), A::Error> {
+        let (key, rest) = match self.path.split_first() {
+            Some(split) => split,
+            None => {
+                let item = T::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                (self.f)(item);
+                return Ok(());
+            }
+        };
+
+        while let Some(k) = map.next_key::<String>()? {
+            if k == *key {
+                map.next_value_seed(Walk {
+                    path: rest,
+                    f: &mut *self.f,
+                    item: PhantomData,
+                })?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the start of the body, cut at a character boundary.
+fn preview(body: &str) -> String {
+    match body.char_indices().nth(PREVIEW_LEN) {
+        Some((i, _)) => body[..i].to_string(),
+        None => body.to_string(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::IndexArtist;
+
+    #[test]
+    fn parse_items_one_at_a_time() {
+        let body = r#"{"subsonic-response": {
+            "status": "ok",
+            "version": "1.16.1",
+            "indexes": {
+                "lastModified": 0,
+                "shortcut": [ { "id": "9", "name": "Podcasts" } ],
+                "index": [
+                    { "name": "A", "artist": [ { "id": "1", "name": "AZALEA" } ] },
+                    { "name": "M", "artist": [
+                        { "id": "2", "name": "Misteur Valaire" },
+                        { "id": "3", "name": "Mouse on Mars" }
+                    ] },
+                    { "name": "Z" }
+                ]
+            }
+        }}"#;
+
+        let path = ["indexes", "index", "artist"];
+        let mut names = Vec::new();
+        parse_items(body, false, &path, |a: IndexArtist| names.push(a.name)).unwrap();
+        assert_eq!(names, ["AZALEA", "Misteur Valaire", "Mouse on Mars"]);
+
+        let xml = r#"<subsonic-response status="ok" version="1.16.1">
+    <indexes lastModified="0">
+        <index name="A"><artist id="1" name="AZALEA"/></index>
+    </indexes>
+</subsonic-response>"#;
+        let mut ids = Vec::new();
+        parse_items(xml, true, &path, |a: IndexArtist| ids.push(a.id)).unwrap();
+        assert_eq!(ids, ["1"]);
+
+        let fail = r#"{"subsonic-response": {
+            "status": "failed",
+            "version": "1.16.1",
+            "error": { "code": 70, "message": "Requested resource not found" }
+        }}"#;
+        let err = parse_items(fail, false, &path, |_: IndexArtist| {}).unwrap_err();
+        assert!(matches!(err, Error::Api(ApiError::NotFound, None)));
+        assert!(parse_items("<html>", false, &path, |_: IndexArtist| {}).is_err());
+    }
 
     #[test]
     fn into_err_result() {
@@ -171,13 +509,110 @@ mod tests {
             }
         }}"#;
         let fail = serde_json::from_str::<Response>(fail).unwrap();
+        assert_eq!(fail.help_url(), None);
         assert!(fail.into_error().is_some());
 
+        let help = r#"{"subsonic-response": {
+            "status": "failed",
+            "version": "1.16.1",
+            "error": {
+                "code": 70,
+                "message": "Requested resource not found",
+                "helpUrl": "https://example.com/errors/70"
+            }
+        }}"#;
+        let help = serde_json::from_str::<Response>(help).unwrap();
+        assert_eq!(help.help_url(), Some("https://example.com/errors/70"));
+
         let success = r#"{"subsonic-response": {
             "status": "ok",
             "version": "1.14.0"
         }}"#;
         let success = serde_json::from_str::<Response>(success).unwrap();
+        assert_eq!(success.version(), Some("1.14.0".into()));
         assert!(success.into_error().is_none());
     }
+
+    #[test]
+    fn parse_foreign_body() {
+        let html = format!("<html><body>{}</body></html>", "Please log in. ".repeat(20));
+        match Response::parse(&html) {
+            Err(Error::InvalidResponse(got)) => {
+                assert!(got.starts_with("<html><body>Please log in."));
+                assert_eq!(got.chars().count(), PREVIEW_LEN);
+            }
+            _ => panic!("expected an invalid response error"),
+        }
+
+        assert!(matches!(
+            Response::parse(r#"{"status": "ok"}"#),
+            Err(Error::InvalidResponse(_))
+        ));
+        assert!(matches!(
+            Response::parse_xml(&html),
+            Err(Error::InvalidResponse(_))
+        ));
+    }
+
+    #[test]
+    fn find_envelope() {
+        let shapes = [
+            r#"{"subsonic-response": {"status": "ok", "version": "1.16.1", "song": {}},
+                "proxy": {"cached": true}}"#,
+            r#"{"data": {"subsonic-response": {"status": "ok", "version": "1.16.1", "song": {}}}}"#,
+            r#"{"status": "ok", "version": "1.16.1", "song": {}}"#,
+        ];
+        for body in shapes {
+            let res = Response::parse(body).unwrap();
+            assert_eq!(res.version(), Some("1.16.1".into()));
+            assert!(res.into_value().is_some());
+        }
+
+        let fail = r#"{"status": "failed", "version": "1.16.1",
+            "error": { "code": 70, "message": "Requested resource not found" }}"#;
+        let err = parse_items(fail, false, &["song"], |_: serde_json::Value| {}).unwrap_err();
+        assert!(matches!(err, Error::Api(ApiError::NotFound, None)));
+
+        let mut ids = Vec::new();
+        let nested = r#"{"data": {"subsonic-response": {"status": "ok", "version": "1.16.1",
+            "indexes": {"index": [{"name": "A", "artist": [{"id": "1", "name": "AZALEA"}]}]}}}}"#;
+        let path = ["indexes", "index", "artist"];
+        parse_items(nested, false, &path, |a: IndexArtist| ids.push(a.id)).unwrap();
+        assert_eq!(ids, ["1"]);
+    }
+
+    #[test]
+    fn parse_truncated_body() {
+        let body = r#"{"subsonic-response": {"status": "ok", "version": "1.16.1", "artists": {"#;
+        assert!(matches!(
+            Response::parse(body),
+            Err(Error::Other(TRUNCATED))
+        ));
+        let items = parse_items::<serde_json::Value, _>(body, false, &["artists"], |_| ());
+        assert!(matches!(items, Err(Error::Other(TRUNCATED))));
+
+        assert!(matches!(
+            Response::parse(""),
+            Err(Error::InvalidResponse(_))
+        ));
+    }
+
+    #[test]
+    fn parse_xml_response() {
+        let fail = r#"<?xml version="1.0" encoding="UTF-8"?>
+<subsonic-response xmlns="http://subsonic.org/restapi" status="failed" version="1.14.0">
+    <error code="70" message="Requested resource not found"/>
+</subsonic-response>"#;
+        let fail = Response::parse_xml(fail).unwrap();
+        assert!(fail.is_err());
+        assert!(matches!(fail.into_error(), Some(ApiError::NotFound)));
+
+        let success = r#"<subsonic-response status="ok" version="1.14.0">
+    <license valid="true" email="foo@bar.com"/>
+</subsonic-response>"#;
+        let success = Response::parse_xml(success).unwrap();
+        assert_eq!(success.version(), Some("1.14.0".into()));
+        let license = success.into_value().unwrap();
+        assert_eq!(license["valid"], true);
+    }
 }
@@ -170,6 +170,7 @@
 //! # fn main() { }
 //! ```
 
+use std::collections::{HashSet, VecDeque};
 use std::fmt;
 
 use crate::song::Song;
@@ -249,7 +250,7 @@ impl fmt::Display for SearchPage {
 }
 
 /// A holder struct for a search result.
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct SearchResult {
     /// Artists found in the search.
     #[serde(rename = "artist")]
@@ -264,3 +265,268 @@ pub struct SearchResult {
     #[serde(default)]
     pub songs: Vec<Song>,
 }
+
+/// The number of results a search matches, from
+/// [`Client::search_count_estimate`].
+///
+/// [`Client::search_count_estimate`]: ../struct.Client.html#method.search_count_estimate
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SearchCounts {
+    /// The number of matching artists.
+    pub artists: usize,
+    /// The number of matching albums.
+    pub albums: usize,
+    /// The number of matching songs.
+    pub songs: usize,
+    /// Whether counting stopped early for any category. If set, the counts
+    /// are lower bounds rather than exact.
+    pub capped: bool,
+}
+
+/// A running count of one category of search results.
+#[derive(Debug, Default)]
+pub(crate) struct Tally {
+    pub(crate) total: usize,
+    pub(crate) done: bool,
+}
+
+impl Tally {
+    /// Adds a page of `found` results, requested `size` at a time. Counting
+    /// stops on a short page, or once `cap` results have been seen; returns
+    /// whether the cap was hit.
+    pub(crate) fn record(&mut self, found: usize, size: usize, cap: usize) -> bool {
+        self.total += found;
+        if found < size {
+            self.done = true;
+            false
+        } else if self.total >= cap {
+            self.done = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the number of results to request next.
+    pub(crate) fn next_count(&self, size: usize) -> usize {
+        if self.done {
+            0
+        } else {
+            size
+        }
+    }
+}
+
+/// A single result from [`Client::search_stream`].
+///
+/// [`Client::search_stream`]: ../struct.Client.html#method.search_stream
+#[derive(Debug, Clone)]
+pub enum SearchHit {
+    /// A matching artist.
+    Artist(Artist),
+    /// A matching album.
+    Album(Album),
+    /// A matching song, boxed as songs are much larger than the others.
+    Song(Box<Song>),
+}
+
+/// The state of a search being paged through one result at a time.
+#[derive(Debug, Default)]
+pub(crate) struct PagedSearch {
+    pub(crate) offset: usize,
+    artists: Tally,
+    albums: Tally,
+    songs: Tally,
+    pending: VecDeque<SearchHit>,
+    seen_artists: HashSet<String>,
+    seen_albums: HashSet<String>,
+    seen_songs: HashSet<String>,
+}
+
+impl PagedSearch {
+    /// Returns the next result from the last page, if any are left.
+    pub(crate) fn pop(&mut self) -> Option<SearchHit> {
+        self.pending.pop_front()
+    }
+
+    /// Checks whether every category has returned a short page.
+    pub(crate) fn done(&self) -> bool {
+        self.artists.done && self.albums.done && self.songs.done
+    }
+
+    /// Stops the search, dropping any pending results.
+    pub(crate) fn stop(&mut self) {
+        self.pending.clear();
+        self.artists.done = true;
+        self.albums.done = true;
+        self.songs.done = true;
+    }
+
+    /// Returns the number of artists, albums and songs to request next.
+    pub(crate) fn next_counts(&self, size: usize) -> [usize; 3] {
+        [
+            self.artists.next_count(size),
+            self.albums.next_count(size),
+            self.songs.next_count(size),
+        ]
+    }
+
+    /// Queues a page of results, requested `size` at a time, skipping any
+    /// that were on an earlier page.
+    ///
+    /// The categories are interleaved so that each kind of result shows up
+    /// as early as possible, rather than every song waiting for the artists
+    /// and albums before it.
+    pub(crate) fn push(&mut self, page: SearchResult, size: usize) {
+        for (tally, found) in [
+            (&mut self.artists, page.artists.len()),
+            (&mut self.albums, page.albums.len()),
+            (&mut self.songs, page.songs.len()),
+        ] {
+            if !tally.done {
+                tally.record(found, size, usize::MAX);
+            }
+        }
+        self.offset += size;
+
+        // Pages can overlap if the library changes while paging, so results
+        // already yielded are dropped.
+        let mut artists = page
+            .artists
+            .into_iter()
+            .filter(|a| self.seen_artists.insert(a.id.clone()))
+            .map(SearchHit::Artist);
+        let mut albums = page
+            .albums
+            .into_iter()
+            .filter(|a| self.seen_albums.insert(a.id.clone()))
+            .map(SearchHit::Album);
+        let mut songs = page
+            .songs
+            .into_iter()
+            .filter(|s| self.seen_songs.insert(s.id.clone()))
+            .map(|s| SearchHit::Song(Box::new(s)));
+        loop {
+            let before = self.pending.len();
+            self.pending.extend(artists.next());
+            self.pending.extend(albums.next());
+            self.pending.extend(songs.next());
+            if self.pending.len() == before {
+                break;
+            }
+        }
+    }
+}
+
+impl SearchResult {
+    /// Removes any result whose name doesn't start with the prefix.
+    pub(crate) fn retain_prefix(&mut self, prefix: &str, articles: &[String]) {
+        self.artists
+            .retain(|a| has_prefix(&a.name, prefix, articles));
+        self.albums
+            .retain(|a| has_prefix(&a.name, prefix, articles));
+        self.songs
+            .retain(|s| has_prefix(&s.title, prefix, articles));
+    }
+}
+
+/// Checks whether the name starts with the prefix, either as-is or after
+/// dropping a leading article. Ignores case.
+fn has_prefix(name: &str, prefix: &str, articles: &[String]) -> bool {
+    let name = name.to_lowercase();
+    let prefix = prefix.to_lowercase();
+    if name.starts_with(&prefix) {
+        return true;
+    }
+
+    articles.iter().any(|article| {
+        let article = article.to_lowercase();
+        name.strip_prefix(&article)
+            .and_then(|rest| rest.strip_prefix(' '))
+            .is_some_and(|rest| rest.trim_start().starts_with(&prefix))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tally_stops_on_short_page() {
+        let mut tally = Tally::default();
+        assert!(!tally.record(50, 50, 1000));
+        assert!(!tally.done);
+        assert!(!tally.record(12, 50, 1000));
+        assert!(tally.done);
+        assert_eq!(tally.total, 62);
+        assert_eq!(tally.next_count(50), 0);
+    }
+
+    #[test]
+    fn tally_stops_at_cap() {
+        let mut tally = Tally::default();
+        assert!(!tally.record(500, 500, 1000));
+        assert!(tally.record(500, 500, 1000));
+        assert!(tally.done);
+        assert_eq!(tally.total, 1000);
+    }
+
+    #[test]
+    fn interleave_paged_hits() {
+        let page = serde_json::from_value::<SearchResult>(serde_json::json!({
+            "artist": [{ "id": "1", "name": "Misteur Valaire", "albumCount": 1 }],
+            "album": [
+                { "id": "1", "name": "Bellevue", "duration": 0, "songCount": 0 },
+                { "id": "2", "name": "Golden Bombay", "duration": 0, "songCount": 0 }
+            ],
+        }))
+        .unwrap();
+
+        let mut search = PagedSearch::default();
+        assert_eq!(search.next_counts(2), [2, 2, 2]);
+        search.push(page, 2);
+        assert!(!search.done());
+        assert_eq!(search.offset, 2);
+        assert_eq!(search.next_counts(2), [0, 2, 0]);
+
+        let ids = std::iter::from_fn(|| search.pop())
+            .map(|hit| match hit {
+                SearchHit::Artist(a) => format!("ar-{}", a.id),
+                SearchHit::Album(a) => format!("al-{}", a.id),
+                SearchHit::Song(s) => s.id.clone(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["ar-1", "al-1", "al-2"]);
+
+        let overlap = serde_json::from_value::<SearchResult>(serde_json::json!({
+            "album": [
+                { "id": "2", "name": "Golden Bombay", "duration": 0, "songCount": 0 },
+                { "id": "3", "name": "Friends", "duration": 0, "songCount": 0 }
+            ],
+        }))
+        .unwrap();
+        search.push(overlap, 2);
+        match search.pop() {
+            Some(SearchHit::Album(a)) => assert_eq!(a.id, "3"),
+            hit => panic!("expected album 3, got {hit:?}"),
+        }
+        assert!(search.pop().is_none());
+        assert!(!search.done());
+
+        search.push(serde_json::from_str("{}").unwrap(), 2);
+        assert!(search.done());
+        assert!(search.pop().is_none());
+    }
+
+    #[test]
+    fn prefix_ignores_case_and_articles() {
+        let articles = vec!["The".to_string(), "Los".to_string()];
+
+        assert!(has_prefix("Beatles", "bea", &articles));
+        assert!(has_prefix("The Beatles", "bea", &articles));
+        assert!(has_prefix("The Beatles", "the b", &articles));
+        assert!(has_prefix("Los Lobos", "LOB", &articles));
+        assert!(!has_prefix("Theatre of Tragedy", "atre", &articles));
+        assert!(!has_prefix("A Beatles Tribute", "bea", &articles));
+    }
+}
@@ -1,5 +1,17 @@
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::thread;
+
 use crate::client;
 use crate::error;
+use crate::query::Query;
+
+/// Query parameters that authenticate a request or pick its format, rather
+/// than selecting what is returned.
+const CLIENT_PARAMS: &[&str] = &["u", "p", "t", "s", "v", "c", "f", "apiKey", "playerId"];
 
 pub fn demo_site() -> error::Result<client::Client> {
     let site = "http://demo.subsonic.org";
@@ -7,3 +19,158 @@ pub fn demo_site() -> error::Result<client::Client> {
     let password = "guest";
     client::Client::new(site, user, password)
 }
+
+/// Returns a client that replays responses from the `fixtures` directory.
+///
+/// Each request is answered with the fixture named after its endpoint and
+/// parameters, such as `getAlbum_id=1.json`. Requests without a fixture are
+/// answered with a 404.
+///
+/// If `SUNK_RECORD_URL` is set, requests are instead sent to that server,
+/// logging in with `SUNK_RECORD_USER` and `SUNK_RECORD_PASSWORD`, and its
+/// responses are saved as fixtures for later runs.
+pub fn fixture_site() -> error::Result<client::Client> {
+    spawn_site(Site::default())
+}
+
+/// Returns a client that replays responses like [`fixture_site`], as an
+/// OpenSubsonic server without API key support.
+///
+/// Fixtures in `fixtures/legacy` take the place of those with the same name,
+/// and requests carrying an `apiKey` are answered with an error, apart from
+/// the public `getOpenSubsonicExtensions`.
+pub fn legacy_fixture_site() -> error::Result<client::Client> {
+    spawn_site(Site {
+        overrides: Some("legacy"),
+        reject_api_keys: true,
+    })
+}
+
+/// How a fixture server answers requests.
+#[derive(Debug, Clone, Copy, Default)]
+struct Site {
+    /// A directory below `fixtures` whose files are served first.
+    overrides: Option<&'static str>,
+    reject_api_keys: bool,
+}
+
+/// The error sent for requests authenticated in a way the server doesn't
+/// support.
+const UNSUPPORTED_AUTH: &str = r#"{"subsonic-response": {"status": "failed", "version": "1.16.1",
+    "error": {"code": 42, "message": "Provided authentication mechanism not supported"}}}"#;
+
+fn spawn_site(site: Site) -> error::Result<client::Client> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let upstream = env::var("SUNK_RECORD_URL").ok().map(|url| {
+        let user = env::var("SUNK_RECORD_USER").unwrap_or_default();
+        let password = env::var("SUNK_RECORD_PASSWORD").unwrap_or_default();
+        client::Client::new(&url, &user, &password).expect("invalid SUNK_RECORD_URL")
+    });
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let _ = serve(stream, site, upstream.as_ref());
+        }
+    });
+
+    client::Client::new(&format!("http://{addr}"), "guest", "guest")
+}
+
+/// Answers one request with its fixture, recording it first if there is an
+/// upstream server.
+fn serve(
+    mut stream: TcpStream,
+    site: Site,
+    upstream: Option<&client::Client>,
+) -> error::Result<()> {
+    let mut request = String::new();
+    let mut reader = BufReader::new(stream.try_clone()?);
+    reader.read_line(&mut request)?;
+    // Skip the headers.
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let target = request.split_whitespace().nth(1).unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let endpoint = path.rsplit('/').next().unwrap_or_default();
+    let all_params = url::form_urlencoded::parse(query.as_bytes()).collect::<Vec<_>>();
+    let params = all_params
+        .iter()
+        .filter(|(k, _)| !CLIENT_PARAMS.contains(&k.as_ref()))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect::<Vec<_>>();
+    let mut file = fixture_path(endpoint, &params);
+    if let Some(dir) = site.overrides {
+        let name = file.file_name().unwrap().to_owned();
+        let over = file.with_file_name(dir).join(name);
+        if over.exists() {
+            file = over;
+        }
+    }
+
+    if let Some(upstream) = upstream {
+        let mut args = Query::new();
+        for (k, v) in &params {
+            args.arg(k, v.as_str());
+        }
+        let body = tokio_test::block_on(upstream.get_raw(endpoint, args))?;
+        fs::create_dir_all(file.parent().unwrap())?;
+        fs::write(&file, body)?;
+    }
+
+    // Extensions are public, so they are listed whatever the authentication.
+    let rejected = site.reject_api_keys
+        && endpoint != "getOpenSubsonicExtensions"
+        && all_params.iter().any(|(k, _)| k == "apiKey");
+    let body = if rejected {
+        Ok(UNSUPPORTED_AUTH.to_string())
+    } else {
+        fs::read_to_string(&file)
+    };
+    let response = match body {
+        Ok(body) => format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            fixture_type(&body),
+            body.len()
+        ),
+        Err(_) => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".into(),
+    };
+    stream.write_all(response.as_bytes())?;
+    Ok(())
+}
+
+/// Picks the MIME type to serve a fixture as: Subsonic responses are JSON,
+/// and anything else stands in for a media file.
+fn fixture_type(body: &str) -> &'static str {
+    if serde_json::from_str::<serde_json::Value>(body).is_ok() {
+        "application/json"
+    } else {
+        "audio/mpeg"
+    }
+}
+
+/// Names the fixture for a request after its endpoint and parameters.
+fn fixture_path(endpoint: &str, params: &[(String, String)]) -> PathBuf {
+    let mut name = endpoint.to_string();
+    for (k, v) in params {
+        name.push('_');
+        name.push_str(k);
+        name.push('=');
+        name.extend(v.chars().filter(char::is_ascii_alphanumeric));
+    }
+    name.push_str(".json");
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join(name)
+}
+
+#[test]
+fn name_fixtures() {
+    let params = [("id".to_string(), "al-1/2".to_string())];
+    assert!(fixture_path("getAlbum", &params).ends_with("fixtures/getAlbum_id=al12.json"));
+    assert!(fixture_path("ping", &[]).ends_with("fixtures/ping.json"));
+}
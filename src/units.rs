@@ -0,0 +1,78 @@
+//! Units of time used by the Subsonic API.
+//!
+//! The API gives some times in seconds, such as stream offsets, and others in
+//! milliseconds, such as scrobble times. Wrapping them in distinct types
+//! stops one being passed where the other is expected.
+
+use std::fmt;
+use std::time::Duration;
+
+/// A number of whole seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Seconds(pub u64);
+
+/// A number of milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Millis(pub u64);
+
+impl Millis {
+    /// Converts to whole seconds, dropping any remainder.
+    pub fn to_seconds(self) -> Seconds {
+        Seconds(self.0 / 1000)
+    }
+}
+
+impl From<Seconds> for Millis {
+    fn from(s: Seconds) -> Millis {
+        Millis(s.0 * 1000)
+    }
+}
+
+impl From<Duration> for Seconds {
+    fn from(d: Duration) -> Seconds {
+        Seconds(d.as_secs())
+    }
+}
+
+impl From<Duration> for Millis {
+    fn from(d: Duration) -> Millis {
+        Millis(d.as_millis() as u64)
+    }
+}
+
+impl From<Seconds> for Duration {
+    fn from(s: Seconds) -> Duration {
+        Duration::from_secs(s.0)
+    }
+}
+
+impl From<Millis> for Duration {
+    fn from(ms: Millis) -> Duration {
+        Duration::from_millis(ms.0)
+    }
+}
+
+impl fmt::Display for Seconds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for Millis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_units() {
+        assert_eq!(Millis::from(Seconds(100)), Millis(100_000));
+        assert_eq!(Millis(1_999).to_seconds(), Seconds(1));
+        assert_eq!(Seconds::from(Duration::from_millis(2_500)), Seconds(2));
+        assert_eq!(Duration::from(Millis(1_500)), Duration::from_millis(1_500));
+    }
+}
@@ -6,7 +6,7 @@ use crate::query::Query;
 use crate::{Client, Result};
 
 /// A struct representing a Subsonic user.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct User {
     /// A user's name.
     pub username: String,
@@ -71,6 +71,7 @@ pub struct User {
     #[serde(rename = "folder")]
     pub folders: Vec<u64>,
     #[serde(default)]
+    #[serde(skip_serializing)]
     _private: bool,
 }
 
@@ -179,7 +180,7 @@ impl User {
             .arg("podcastRole", self.podcast_role)
             .arg("shareRole", self.share_role)
             .arg("videoConversionRole", self.video_conversion_role)
-            .arg_list("musicFolderId", &self.folders.clone())
+            .arg_list("musicFolderId", &self.folders)
             .arg("maxBitRate", self.max_bit_rate)
             .build();
         client.get("updateUser", args).await?;
@@ -0,0 +1,121 @@
+//! Helpers for working with fetched items.
+
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer, Serializer};
+
+use crate::song::Song;
+
+/// Returns the total running time of the songs.
+///
+/// Songs without a known duration are skipped, so the total may be shorter
+/// than the real running time.
+pub fn total_duration(songs: &[Song]) -> Duration {
+    let secs = songs.iter().filter_map(|s| s.duration).sum();
+    Duration::from_secs(secs)
+}
+
+/// Serializes a value using its `Display` implementation.
+///
+/// Subsonic sends most IDs as strings, even where `sunk` stores them as
+/// numbers; this keeps re-serialized values compatible with the API.
+pub(crate) fn serialize_display<T, S>(value: &T, ser: S) -> Result<S::Ok, S::Error>
+where
+    T: fmt::Display,
+    S: Serializer,
+{
+    ser.collect_str(value)
+}
+
+/// Deserializes an optional string, treating `null` and `""` as missing.
+///
+/// Some servers send empty values for fields they don't have, rather than
+/// leaving them out.
+pub(crate) fn empty_as_none<'de, D>(de: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = Option::<String>::deserialize(de)?;
+    Ok(s.filter(|s| !s.is_empty()))
+}
+
+/// Deserializes a list that may be sent as a single object.
+///
+/// Subsonic's JSON is converted from XML, so a repeated element with only one
+/// occurrence can arrive as an object rather than a one-item array.
+pub(crate) fn one_or_many<'de, D, T>(de: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    Ok(match Option::<OneOrMany<T>>::deserialize(de)? {
+        Some(OneOrMany::One(t)) => vec![t],
+        Some(OneOrMany::Many(v)) => v,
+        None => Vec::new(),
+    })
+}
+
+/// Names that Windows reserves for devices, regardless of extension.
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Makes a file name (without its extension) safe to use on common
+/// filesystems.
+///
+/// Replaces characters that are illegal on Windows or Unix, drops trailing
+/// dots and spaces, and avoids names that Windows reserves.
+pub(crate) fn sanitize_file_stem(stem: &str) -> String {
+    let clean: String = stem
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let clean = clean.trim().trim_end_matches(['.', ' ']);
+
+    if clean.is_empty() {
+        "_".to_string()
+    } else if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(clean)) {
+        format!("_{clean}")
+    } else {
+        clean.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(id: &str, duration: Option<u64>) -> Song {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "title": "Avenue",
+            "size": 0,
+            "contentType": "audio/mpeg",
+            "suffix": "mp3",
+            "isVideo": false,
+            "type": "music",
+            "duration": duration,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn sum_known_durations() {
+        let songs = [song("1", Some(198)), song("2", None), song("3", Some(2))];
+        assert_eq!(total_duration(&songs), Duration::from_secs(200));
+        assert_eq!(total_duration(&[]), Duration::ZERO);
+    }
+}
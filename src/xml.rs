@@ -0,0 +1,283 @@
+//! Conversion of XML responses into the same shape as JSON ones.
+//!
+//! The Subsonic API describes XML and JSON responses with one schema, so an
+//! XML response is translated into the JSON the server would have sent and
+//! then parsed as usual. XML carries no types or list markers, which are
+//! recovered from what the API documents for each key.
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde_json::{Map, Number, Value};
+
+/// Keys whose values are numbers in JSON responses.
+const NUMERIC_KEYS: &[&str] = &[
+    "albumCount",
+    "albumGain",
+    "albumPeak",
+    "averageRating",
+    "baseGain",
+    "bitDepth",
+    "bitRate",
+    "bookmarkPosition",
+    "channelCount",
+    "code",
+    "count",
+    "currentIndex",
+    "discNumber",
+    "duration",
+    "fallbackGain",
+    "folder",
+    "gain",
+    "lastModified",
+    "maxBitRate",
+    "minutesAgo",
+    "offset",
+    "originalHeight",
+    "originalWidth",
+    "playCount",
+    "playerId",
+    "position",
+    "samplingRate",
+    "size",
+    "songCount",
+    "time",
+    "track",
+    "trackGain",
+    "trackPeak",
+    "userRating",
+    "versions",
+    "year",
+];
+
+/// Elements holding only text that are lists in JSON responses.
+const TEXT_LISTS: &[&str] = &["folder", "versions"];
+
+/// Nested elements that are single objects in JSON responses, rather than
+/// lists.
+const OBJECTS: &[&str] = &["replayGain"];
+
+/// Elements directly inside the response that are lists in JSON responses.
+const TOP_LEVEL_LISTS: &[&str] = &["openSubsonicExtensions"];
+
+/// An element that has been opened but not yet closed.
+struct Node {
+    name: String,
+    attrs: Map<String, Value>,
+    text: String,
+    children: Vec<(String, Value, bool)>,
+}
+
+impl Node {
+    fn open(start: &BytesStart) -> Option<Node> {
+        let mut attrs = Map::new();
+        for attr in start.attributes() {
+            let attr = attr.ok()?;
+            let key = std::str::from_utf8(attr.key.as_ref()).ok()?;
+            if key == "xmlns" || key.starts_with("xmlns:") {
+                continue;
+            }
+            let value = attr.unescape_value().ok()?;
+            attrs.insert(key.to_string(), typed(key, &value));
+        }
+
+        Some(Node {
+            name: std::str::from_utf8(start.name().as_ref()).ok()?.to_string(),
+            attrs,
+            text: String::new(),
+            children: Vec::new(),
+        })
+    }
+
+    /// Closes the element, returning its value and whether it was a full
+    /// element rather than only text.
+    fn close(self, depth: usize) -> (String, Value, bool) {
+        if self.attrs.is_empty() && self.children.is_empty() {
+            let value = typed(&self.name, &self.text);
+            return (self.name, value, false);
+        }
+
+        let mut map = self.attrs;
+        if !self.text.is_empty() {
+            map.insert("value".into(), Value::String(self.text));
+        }
+
+        let mut groups: Vec<(String, Vec<Value>, bool)> = Vec::new();
+        for (name, value, element) in self.children {
+            match groups.iter_mut().find(|g| g.0 == name) {
+                Some(group) => group.1.push(value),
+                None => groups.push((name, vec![value], element)),
+            }
+        }
+
+        for (name, mut values, element) in groups {
+            let is_list = if depth == 0 {
+                TOP_LEVEL_LISTS.contains(&name.as_str())
+            } else if OBJECTS.contains(&name.as_str()) {
+                false
+            } else {
+                element || TEXT_LISTS.contains(&name.as_str())
+            };
+            let value = if values.len() > 1 || is_list {
+                Value::Array(values)
+            } else {
+                values.remove(0)
+            };
+            map.insert(name, value);
+        }
+
+        (self.name, Value::Object(map), true)
+    }
+}
+
+/// Gives an XML value the type it would have in a JSON response.
+fn typed(key: &str, value: &str) -> Value {
+    match value {
+        "true" => return Value::Bool(true),
+        "false" => return Value::Bool(false),
+        _ => {}
+    }
+
+    if NUMERIC_KEYS.contains(&key) {
+        if let Ok(n) = value.parse::<i64>() {
+            return Value::Number(n.into());
+        }
+        if let Some(n) = value.parse::<f64>().ok().and_then(Number::from_f64) {
+            return Value::Number(n);
+        }
+    }
+    Value::String(value.to_string())
+}
+
+/// Converts an XML response body into its JSON equivalent.
+///
+/// Returns `None` if the body isn't well-formed XML.
+pub(crate) fn to_json(body: &str) -> Option<Value> {
+    let mut reader = Reader::from_str(body);
+    reader.trim_text(true);
+
+    let mut stack: Vec<Node> = Vec::new();
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(start) => stack.push(Node::open(&start)?),
+            Event::Empty(start) => {
+                let closed = Node::open(&start)?.close(stack.len());
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(closed),
+                    None => return Some(root(closed)),
+                }
+            }
+            Event::Text(text) => {
+                if let Some(node) = stack.last_mut() {
+                    node.text.push_str(&text.unescape().ok()?);
+                }
+            }
+            Event::CData(data) => {
+                if let Some(node) = stack.last_mut() {
+                    node.text.push_str(std::str::from_utf8(&data).ok()?);
+                }
+            }
+            Event::End(_) => {
+                let node = stack.pop()?;
+                let closed = node.close(stack.len());
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(closed),
+                    None => return Some(root(closed)),
+                }
+            }
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+fn root((name, value, _): (String, Value, bool)) -> Value {
+    let mut map = Map::new();
+    map.insert(name, value);
+    Value::Object(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_album() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+<subsonic-response xmlns="http://subsonic.org/restapi" status="ok" version="1.16.1">
+    <album id="1" name="1999" songCount="1" duration="210">
+        <song id="27" title="Bellevue Avenue" track="1" isDir="false"/>
+    </album>
+</subsonic-response>"#;
+
+        let value = to_json(body).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "subsonic-response": {
+                    "status": "ok",
+                    "version": "1.16.1",
+                    "album": {
+                        "id": "1",
+                        "name": "1999",
+                        "songCount": 1,
+                        "duration": 210,
+                        "song": [ {
+                            "id": "27",
+                            "title": "Bellevue Avenue",
+                            "track": 1,
+                            "isDir": false
+                        } ]
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn convert_text_elements() {
+        let body = r#"<subsonic-response status="ok" version="1.16.1">
+    <user username="guest" adminRole="false">
+        <folder>1</folder>
+        <folder>3</folder>
+    </user>
+    <lyrics artist="Misteur Valaire">A &amp; B</lyrics>
+    <openSubsonicExtensions name="songLyrics"><versions>1</versions></openSubsonicExtensions>
+</subsonic-response>"#;
+
+        let value = to_json(body).unwrap();
+        let inner = &value["subsonic-response"];
+        assert_eq!(inner["user"]["folder"], serde_json::json!([1, 3]));
+        assert_eq!(inner["user"]["adminRole"], false);
+        assert_eq!(inner["lyrics"]["value"], "A & B");
+        assert_eq!(
+            inner["openSubsonicExtensions"],
+            serde_json::json!([ { "name": "songLyrics", "versions": [1] } ])
+        );
+    }
+
+    #[test]
+    fn parse_song_with_replay_gain() {
+        let body = r#"<subsonic-response status="ok" version="1.16.1">
+    <song id="27" title="Bellevue Avenue" size="5400185" contentType="audio/mpeg"
+          suffix="mp3" isVideo="false" type="music">
+        <replayGain trackGain="-6.5" trackPeak="0.98"/>
+    </song>
+</subsonic-response>"#;
+
+        let value = crate::response::Response::parse_xml(body)
+            .unwrap()
+            .into_value()
+            .unwrap();
+        assert!(value["replayGain"].is_object());
+        let song = serde_json::from_value::<crate::Song>(value).unwrap();
+        let gain = song.replay_gain.unwrap();
+        assert_eq!(gain.track_gain, Some(-6.5));
+        assert_eq!(gain.track_peak, Some(0.98));
+    }
+
+    #[test]
+    fn reject_malformed() {
+        assert!(to_json("<subsonic-response status=\"ok\">").is_none());
+        assert!(to_json("{\"subsonic-response\": {}}").is_none());
+    }
+}